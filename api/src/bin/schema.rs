@@ -0,0 +1,98 @@
+use std::{env, path::PathBuf, process::exit};
+
+use api::{
+    client::WildberriesClient,
+    log::{set_sink, Level},
+    schema::{check, update, Sample},
+    site::{Site, DEFAULT_SITE_URL},
+};
+
+static USAGE: &str = "Usage: schema --cod <cod1S> --golden <dir> [--site <url>] \
+                      [--token <token>] [--update]";
+static TOKEN_ENV: &str = "SDP_TOKEN";
+
+/// Exit codes: 0 - responses match the models and the golden samples, 1 - schema drift,
+/// 2 - bad arguments
+#[tokio::main]
+async fn main() {
+    // Only failed requests are worth printing next to the report
+    set_sink(|record| {
+        if record.level >= Level::Warn {
+            eprintln!("{}", record);
+        }
+    });
+
+    let mut site = Site::from_env();
+    let mut token = env::var(TOKEN_ENV).ok();
+    let mut cod = None;
+    let mut golden = None;
+    let mut store = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--site" => match args.next().and_then(|a| Site::new(&a)) {
+                Some(value) => site = Some(value),
+                None => fail("--site expects an http(s) url"),
+            },
+            "--token" => match args.next() {
+                Some(value) => token = Some(value),
+                None => fail("--token expects the account token"),
+            },
+            "--cod" => match args.next().map(|a| a.parse::<u64>()) {
+                Some(Ok(value)) => cod = Some(value),
+                _ => fail("--cod expects a variant id"),
+            },
+            "--golden" => match args.next() {
+                Some(value) => golden = Some(PathBuf::from(value)),
+                None => fail("--golden expects a directory"),
+            },
+            "--update" => store = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                println!("The site and the token are also read from SDP_SITE_URL and SDP_TOKEN");
+                return;
+            }
+            _ => fail(&format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    let cod = cod.unwrap_or_else(|| fail("--cod is required"));
+    let golden = golden
+        .unwrap_or_else(|| fail("--golden is required (api/tests/fixtures in the repository)"));
+    let site = site.unwrap_or_else(|| Site::new(DEFAULT_SITE_URL).unwrap());
+    let mut client = WildberriesClient::new(site, None, token.as_deref());
+
+    let mut samples = vec![Sample::Product(cod)];
+    if token.is_some() {
+        samples.push(Sample::Basket);
+    } else {
+        eprintln!("No token, the basket is skipped");
+    }
+
+    let mut ok = true;
+    for sample in samples {
+        if store {
+            match update(&mut client, sample, &golden).await {
+                Ok(()) => println!("{}: stored in {}", sample.name(), golden.display()),
+                Err(err) => {
+                    eprintln!("{}: can't store: {}", sample.name(), err);
+                    ok = false;
+                }
+            }
+        } else {
+            let report = check(&mut client, sample, &golden).await;
+            print!("{}", report);
+            ok &= report.is_ok();
+        }
+    }
+
+    if !ok {
+        exit(1);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    exit(2)
+}
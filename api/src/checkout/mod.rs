@@ -0,0 +1,561 @@
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+
+use crate::{
+    client::WildberriesClient,
+    error::{Error, ErrorKind},
+    log::{Level, Record},
+    misc::rand_millis,
+    models::{Basket, ProductCard, Size, Variant},
+    transcript::Transcript,
+};
+
+pub use steps::{
+    order_form, AddToCart, AwaitDrop, CheckAvailability, ClearCart, CollectCart, ConfirmOrder,
+    FetchCart, Monitor, PaymentError, Schedule, SubmitOrder, TokenCheck, UserLocation,
+};
+
+mod steps;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Checkout step
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A single unit of the checkout, e.g. one request to the site
+#[async_trait]
+pub trait CheckoutStep: Send + Sync {
+    /// Label used in error messages, stays the same when steps are reordered
+    fn tier(&self) -> &'static str;
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome;
+}
+
+/// Tells the driver what to do after a step has run
+#[derive(Clone, Debug)]
+pub enum StepOutcome {
+    /// Run the next step of the current stage
+    Continue,
+
+    /// Report progress, then run the next step of the current stage
+    Break(Option<String>),
+    /// Report progress, then run the same step again
+    Wait(String),
+    /// Enter another stage from its first step
+    Move(Stage, Option<String>),
+
+    /// Transient error, the step runs again while the retry policy allows it
+    Retry(Error),
+    Error(Error),
+    Complete(Option<String>),
+    Failed(Option<String>),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stage {
+    Start,
+    /// Waiting for the warmup before a scheduled drop
+    Scheduled,
+    Warmup,
+    Waiting,
+    Process,
+    End,
+}
+
+#[derive(Clone, Debug)]
+pub enum EndKind {
+    /// Order is placed, the flag is set when it is confirmed by the site
+    Succeed(bool),
+    /// 3-D Secure payment confirmation is required
+    UserAction,
+    Failed,
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Context
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A line of the order
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Item {
+    pub card: ProductCard,
+    pub variant: Variant,
+    /// Size to be checked out, replaced by the first available one of `sizes` on the availability
+    /// check
+    pub size: Size,
+    /// Ids of acceptable sizes in priority order, any size of the variant if empty
+    pub sizes: Vec<u64>,
+    pub quantity: u64,
+}
+
+impl Item {
+    pub fn new(card: ProductCard, variant: Variant, size: Size, quantity: u64) -> Item {
+        Item {
+            card,
+            variant,
+            sizes: vec![size.id],
+            size,
+            quantity,
+        }
+    }
+
+    /// Sets acceptable sizes in priority order, an empty list accepts any size
+    pub fn sizes(mut self, sizes: Vec<u64>) -> Item {
+        self.sizes = sizes;
+        self
+    }
+
+    /// Names of acceptable sizes, e.g. "M / L"
+    pub fn sizes_name(&self) -> String {
+        if self.variant.sizes.len() == 1 {
+            String::from("-")
+        } else if self.sizes.is_empty() {
+            String::from("Any")
+        } else {
+            self.sizes
+                .iter()
+                .map(|id| match self.variant.sizes.get(&id.to_string()) {
+                    Some(size) => size.name.as_str(),
+                    None => "?",
+                })
+                .collect::<Vec<&str>>()
+                .join(" / ")
+        }
+    }
+
+    /// Size name, or "-" for one size products
+    pub fn size_name(&self) -> &str {
+        if self.variant.sizes.len() == 1 {
+            "-"
+        } else {
+            &self.size.name
+        }
+    }
+}
+
+/// Everything the steps share during a single checkout
+pub struct Ctx {
+    pub client: WildberriesClient,
+    pub items: Vec<Item>,
+    /// (limiter, force, monitor, monitor frequency)
+    pub flags: (bool, bool, bool, u64),
+    pub guard: PriceGuard,
+    /// Time the product goes live, the availability isn't checked before it
+    pub drop: Option<SystemTime>,
+    /// How long before the drop the warmup starts
+    pub lead: Duration,
+
+    /// Cart contents, first as found on warmup and then as submitted
+    pub cart: Basket,
+    /// Lines (size id, quantity) this checkout has added to the cart
+    pub added: Vec<(u64, u64)>,
+    /// Redirect url returned on order submit
+    pub order: Option<String>,
+    /// Content (order url, payment url or bank error) and kind of the result
+    pub end: Option<(String, EndKind)>,
+    pub start: SystemTime,
+}
+
+impl Ctx {
+    pub fn new(client: WildberriesClient, items: Vec<Item>, flags: (bool, bool, bool, u64)) -> Ctx {
+        Ctx {
+            client,
+            items,
+            flags,
+            guard: PriceGuard::default(),
+            drop: None,
+            lead: Duration::from_secs(0),
+            cart: Basket::default(),
+            added: Vec::new(),
+            order: None,
+            end: None,
+            start: SystemTime::now(),
+        }
+    }
+
+    pub fn guard(mut self, guard: PriceGuard) -> Ctx {
+        self.guard = guard;
+        self
+    }
+
+    /// Starts the warmup `lead` before `drop` and the availability checks at `drop`
+    pub fn schedule(mut self, drop: SystemTime, lead: Duration) -> Ctx {
+        self.drop = Some(drop);
+        self.lead = lead;
+        self
+    }
+
+    /// Random delay (ms) from `range` if the limiter is on
+    pub fn delay(&self, range: RangeInclusive<u64>) -> u64 {
+        if self.flags.0 {
+            rand_millis(range)
+        } else {
+            0
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Flow
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Steps of every stage in the order they are entered. The steps of a stage run in a loop until
+/// one of them leaves it
+#[derive(Default)]
+pub struct Flow {
+    stages: Vec<(Stage, Vec<Box<dyn CheckoutStep>>)>,
+
+    retry: RetryPolicy,
+    /// Retry policies of particular tiers
+    tier_retry: Vec<(&'static str, RetryPolicy)>,
+}
+
+impl Flow {
+    pub fn new() -> Flow {
+        Flow::default()
+    }
+
+    /// Scheduled, warmup, waiting and process stages of the regular checkout. The end stage is
+    /// left to the caller
+    pub fn checkout() -> Flow {
+        Flow::new()
+            .stage(Stage::Scheduled, vec![Box::new(Schedule)])
+            .stage(
+                Stage::Warmup,
+                vec![
+                    Box::new(TokenCheck),
+                    Box::new(UserLocation),
+                    Box::new(FetchCart),
+                    Box::new(ClearCart),
+                ],
+            )
+            .stage(
+                Stage::Waiting,
+                vec![
+                    Box::new(AwaitDrop),
+                    Box::new(CheckAvailability),
+                    Box::new(Monitor),
+                ],
+            )
+            .stage(
+                Stage::Process,
+                vec![
+                    Box::new(AddToCart),
+                    Box::new(CollectCart),
+                    Box::new(SubmitOrder),
+                    Box::new(ConfirmOrder),
+                    Box::new(PaymentError),
+                ],
+            )
+            // A timed out submit may still place the order, so it's never repeated
+            .retry_tier(SubmitOrder.tier(), RetryPolicy::none())
+    }
+
+    /// Sets the steps of `stage`, replacing the previous ones
+    pub fn stage(mut self, stage: Stage, steps: Vec<Box<dyn CheckoutStep>>) -> Flow {
+        match self.stages.iter_mut().find(|(s, _)| *s == stage) {
+            Some(entry) => entry.1 = steps,
+            None => self.stages.push((stage, steps)),
+        }
+
+        self
+    }
+
+    pub fn steps(&self, stage: Stage) -> &[Box<dyn CheckoutStep>] {
+        match self.stages.iter().find(|(s, _)| *s == stage) {
+            Some((_, steps)) => steps,
+            None => &[],
+        }
+    }
+
+    /// Sets the retry policy of the tiers without their own one
+    pub fn retry(mut self, policy: RetryPolicy) -> Flow {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets the retry policy of `tier`
+    pub fn retry_tier(mut self, tier: &'static str, policy: RetryPolicy) -> Flow {
+        match self.tier_retry.iter_mut().find(|(t, _)| *t == tier) {
+            Some(entry) => entry.1 = policy,
+            None => self.tier_retry.push((tier, policy)),
+        }
+
+        self
+    }
+
+    pub fn policy(&self, tier: &str) -> RetryPolicy {
+        match self.tier_retry.iter().find(|(t, _)| *t == tier) {
+            Some((_, policy)) => *policy,
+            None => self.retry,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Retry policy
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Upper bound of the backoff between attempts (ms)
+pub const MAX_BACKOFF: u64 = 30_000;
+
+#[derive(Serialize, Deserialize, PartialEq, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Max number of retries of a step after a transient error
+    pub attempts: u32,
+    /// Delay (ms) before the first retry, doubled on every next one
+    pub backoff: u64,
+    /// Max random delay (ms) added to the backoff
+    pub jitter: u64,
+}
+
+impl RetryPolicy {
+    /// Fails on the first transient error
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 0,
+            backoff: 0,
+            jitter: 0,
+        }
+    }
+
+    /// Delay (ms) before the retry `attempt` (starting from 1)
+    pub fn delay(&self, attempt: u32) -> u64 {
+        let backoff = self
+            .backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(MAX_BACKOFF);
+
+        if self.jitter == 0 {
+            backoff
+        } else {
+            backoff + thread_rng().gen_range(0..=self.jitter)
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff: 500,
+            jitter: 250,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Price guard
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Limits checked on the availability check and again on the cart total before the order is
+/// submitted
+#[derive(Serialize, Deserialize, Default, PartialEq, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct PriceGuard {
+    /// Max total price of the order (RUB)
+    pub max_price: Option<u64>,
+    /// Min discount of every item (%)
+    pub min_discount: Option<u64>,
+}
+
+impl PriceGuard {
+    /// Checks the prices of the sizes to be checked out
+    pub fn check_items(&self, items: &[Item]) -> Result<(), String> {
+        if let Some(min) = self.min_discount {
+            for item in items {
+                let size = &item.size;
+                let discount = match size.price {
+                    0 => 0,
+                    price => price.saturating_sub(size.sale_price) * 100 / price,
+                };
+
+                if discount < min {
+                    return Err(format!(
+                        "Discount {}% of {} is below {}%",
+                        discount, item.card.name, min
+                    ));
+                }
+            }
+        }
+
+        self.check_total(items.iter().map(|i| i.size.sale_price * i.quantity).sum())
+    }
+
+    pub fn check_total(&self, total: u64) -> Result<(), String> {
+        match self.max_price {
+            Some(max) if total > max => Err(format!(
+                "Price {} RUB is above the max of {} RUB",
+                total, max
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Driver
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Runs the steps of a flow one by one and keeps track of the current position
+pub struct Checkout {
+    flow: Flow,
+    pub ctx: Ctx,
+
+    stage: Stage,
+    step: usize,
+    /// Retries of the current step so far
+    attempt: u32,
+    /// Delay (ms) before the next retry
+    backoff: u64,
+    /// Requests of the tier which failed last, if the client captures them
+    transcript: Option<Transcript>,
+}
+
+impl Checkout {
+    pub fn new(flow: Flow, ctx: Ctx) -> Checkout {
+        Checkout {
+            flow,
+            ctx,
+            stage: Stage::Start,
+            step: 0,
+            attempt: 0,
+            backoff: 0,
+            transcript: None,
+        }
+    }
+
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Requests and responses of the tier which stopped the checkout with an error
+    pub fn transcript(&self) -> Option<&Transcript> {
+        self.transcript.as_ref()
+    }
+
+    /// Number of the retry being made and the max number of retries of the current step
+    pub fn attempt(&self) -> (u32, u32) {
+        (self.attempt, self.current_policy().attempts)
+    }
+
+    /// Continues a stopped checkout from `stage`, keeping the context (cart and cookies). The
+    /// failed step runs again if it belongs to `stage`, otherwise the stage starts over. Items
+    /// this checkout has already added to the cart are not added again
+    pub fn resume(&mut self, stage: Stage) {
+        if stage != self.stage {
+            self.stage = stage;
+            self.step = 0;
+        }
+
+        self.attempt = 0;
+        self.backoff = 0;
+        self.ctx.order = None;
+        self.ctx.end = None;
+    }
+
+    fn current_policy(&self) -> RetryPolicy {
+        match self.flow.steps(self.stage).get(self.step) {
+            Some(step) => self.flow.policy(step.tier()),
+            None => self.flow.retry,
+        }
+    }
+
+    /// Runs the current step and moves to the next one according to its outcome. Never stops by
+    /// itself, the caller decides what to do after errors and completion
+    ///
+    /// A transient error is returned as [`StepOutcome::Retry`] while there are attempts left (the
+    /// backoff is waited out on the next call) and as [`StepOutcome::Error`] after that
+    pub async fn step(&mut self) -> StepOutcome {
+        if self.backoff != 0 {
+            sleep(Duration::from_millis(self.backoff)).await;
+            self.backoff = 0;
+        }
+
+        let outcome = match self.stage {
+            Stage::Start => match self.flow.stages.first() {
+                Some((stage, _)) => StepOutcome::Move(*stage, None),
+                None => StepOutcome::Error(ErrorKind::Internal(String::from("Empty flow")).into()),
+            },
+            stage => match self.flow.steps(stage).get(self.step) {
+                Some(step) => {
+                    self.ctx.client.set_tier(step.tier());
+                    step.run(&mut self.ctx).await
+                }
+                None => StepOutcome::Error(
+                    ErrorKind::Internal(format!("No steps in {:?} stage", stage)).into(),
+                ),
+            },
+        };
+
+        let outcome = match outcome {
+            StepOutcome::Retry(err) => {
+                let policy = self.current_policy();
+
+                if self.attempt < policy.attempts {
+                    self.attempt += 1;
+                    self.backoff = policy.delay(self.attempt);
+                    Record::new(
+                        Level::Warn,
+                        self.ctx.client.tag(),
+                        format!(
+                            "{}, retry {}/{} in {} ms",
+                            err, self.attempt, policy.attempts, self.backoff
+                        ),
+                    )
+                    .log();
+                    return StepOutcome::Retry(err);
+                }
+
+                StepOutcome::Error(err)
+            }
+            outcome => outcome,
+        };
+        self.attempt = 0;
+        self.log(&outcome);
+
+        if let StepOutcome::Error(ref err) = outcome {
+            self.transcript = self.ctx.client.transcript(&err.to_string());
+        }
+
+        match outcome {
+            StepOutcome::Continue | StepOutcome::Break(_) => {
+                self.step = (self.step + 1) % self.flow.steps(self.stage).len().max(1)
+            }
+            StepOutcome::Move(stage, _) => {
+                self.stage = stage;
+                self.step = 0;
+            }
+            _ => {}
+        }
+
+        outcome
+    }
+
+    fn log(&self, outcome: &StepOutcome) {
+        let (level, message) = match outcome {
+            StepOutcome::Continue | StepOutcome::Retry(_) => return,
+            StepOutcome::Break(None) => (Level::Debug, String::from("Step done")),
+            StepOutcome::Break(Some(msg)) | StepOutcome::Wait(msg) => (Level::Debug, msg.clone()),
+            StepOutcome::Move(stage, _) => (Level::Info, format!("{:?} stage", stage)),
+            StepOutcome::Error(err) => (Level::Error, format!("[E{}] {}", err.code(), err)),
+            StepOutcome::Complete(msg) => (
+                Level::Info,
+                format!("Complete: {}", msg.as_deref().unwrap_or("-")),
+            ),
+            StepOutcome::Failed(msg) => (
+                Level::Warn,
+                format!("Failed: {}", msg.as_deref().unwrap_or("-")),
+            ),
+        };
+
+        Record::new(level, self.ctx.client.tag(), message).log();
+    }
+}
@@ -0,0 +1,613 @@
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use tokio::time::sleep;
+
+use super::{CheckoutStep, Ctx, EndKind, Stage, StepOutcome};
+use crate::{
+    error::{Error, ErrorKind},
+    misc::retrieve,
+    models::{Basket, ResponseResult, ResponseValue, Size},
+};
+
+/// Transient client errors are retried, the rest stop the checkout
+fn failure(err: Error, tier: &str) -> StepOutcome {
+    let err = err.tier(tier);
+
+    if err.is_transient() {
+        StepOutcome::Retry(err)
+    } else {
+        StepOutcome::Error(err)
+    }
+}
+
+fn error(kind: ErrorKind, tier: &str) -> StepOutcome {
+    StepOutcome::Error(Error::new(kind).tier(tier))
+}
+
+/// Result of the step which is missing `part`
+fn scheme(part: &str, tier: &str) -> StepOutcome {
+    error(ErrorKind::Scheme(part.to_string()), tier)
+}
+
+/// Refused request, with the reason given by the site
+fn rejected(result: &ResponseResult, tier: &str) -> StepOutcome {
+    error(
+        ErrorKind::Rejected {
+            state: result.state,
+            message: result.message(),
+            status: result.status,
+        },
+        tier,
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Schedule
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Sleeps up to a second towards `at` and returns the time left, none once it has come
+async fn countdown(at: SystemTime) -> Option<Duration> {
+    let left = at.duration_since(SystemTime::now()).unwrap_or_default();
+    if left.is_zero() {
+        return None;
+    }
+
+    // Whole seconds are left after the first tick
+    let tick = match left.subsec_nanos() {
+        0 => Duration::from_secs(1),
+        nanos => Duration::from_nanos(nanos as u64),
+    };
+    sleep(tick).await;
+
+    Some(left - tick)
+}
+
+fn clock(left: Duration) -> String {
+    let secs = left.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Holds the task until the warmup lead time before the drop
+pub struct Schedule;
+
+#[async_trait]
+impl CheckoutStep for Schedule {
+    fn tier(&self) -> &'static str {
+        "S"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let start = match ctx.drop {
+            Some(drop) => drop.checked_sub(ctx.lead).unwrap_or(drop),
+            None => return StepOutcome::Move(Stage::Warmup, None),
+        };
+
+        match countdown(start).await {
+            Some(left) => StepOutcome::Wait(format!("Starts in {}", clock(left))),
+            None => StepOutcome::Move(Stage::Warmup, None),
+        }
+    }
+}
+
+/// Holds the availability check until the drop
+pub struct AwaitDrop;
+
+#[async_trait]
+impl CheckoutStep for AwaitDrop {
+    fn tier(&self) -> &'static str {
+        "E/S"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        match ctx.drop {
+            Some(drop) => match countdown(drop).await {
+                Some(left) => StepOutcome::Wait(format!("Drop in {}", clock(left))),
+                None => StepOutcome::Continue,
+            },
+            None => StepOutcome::Continue,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Warmup
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Checks that the account token is still valid
+pub struct TokenCheck;
+
+#[async_trait]
+impl CheckoutStep for TokenCheck {
+    fn tier(&self) -> &'static str {
+        "A"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        match ctx.client.personal_cabinet_data(0).await {
+            Ok(result) if result.state == -1 => error(ErrorKind::TokenExpired, self.tier()),
+            Ok(_) => StepOutcome::Continue,
+            Err(err) => failure(err, self.tier()),
+        }
+    }
+}
+
+/// Receives the user location cookie
+pub struct UserLocation;
+
+#[async_trait]
+impl CheckoutStep for UserLocation {
+    fn tier(&self) -> &'static str {
+        "B"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let delay = ctx.delay(5..=10);
+
+        match ctx.client.user_location(delay).await {
+            Ok(result) if result.state == -1 => rejected(&result, self.tier()),
+            Ok(_) => StepOutcome::Continue,
+            Err(err) => failure(err, self.tier()),
+        }
+    }
+}
+
+/// Looks for other products in the cart. With the force flag errors are ignored
+pub struct FetchCart;
+
+#[async_trait]
+impl CheckoutStep for FetchCart {
+    fn tier(&self) -> &'static str {
+        "C"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let delay = ctx.delay(10..=20);
+
+        let outcome = match ctx.client.basket_data(delay).await {
+            Ok(result) if result.state == 0 => match result.value {
+                ResponseValue::Value(value) => match value.data.basket {
+                    Some(basket) => {
+                        ctx.cart = basket;
+                        return StepOutcome::Continue;
+                    }
+                    None => scheme("B", self.tier()),
+                },
+                _ => {
+                    ctx.cart = Basket::default();
+                    return StepOutcome::Continue;
+                }
+            },
+            Ok(result) => rejected(&result, self.tier()),
+            Err(err) if matches!(err.kind, ErrorKind::Response { .. }) => failure(err, self.tier()),
+            Err(err) => return failure(err, self.tier()),
+        };
+
+        force(ctx, outcome)
+    }
+}
+
+/// Removes other products found by [`FetchCart`] and starts waiting for the product
+pub struct ClearCart;
+
+#[async_trait]
+impl CheckoutStep for ClearCart {
+    fn tier(&self) -> &'static str {
+        "D"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        if ctx.cart.order_items.is_empty() {
+            return scanning();
+        }
+
+        let delay = ctx.delay(5..=10);
+        let items = ctx.cart.order_items.clone();
+
+        let outcome = match ctx.client.basket_delete(&items, delay).await {
+            Ok(result) if result.state == -1 => rejected(&result, self.tier()),
+            Ok(_) => scanning(),
+            Err(err) => failure(err, self.tier()),
+        };
+
+        // The products found on warmup aren't in the cart anymore
+        let outcome = force(ctx, outcome);
+        if matches!(outcome, StepOutcome::Move(..)) {
+            ctx.cart = Basket::default();
+        }
+
+        outcome
+    }
+}
+
+fn scanning() -> StepOutcome {
+    StepOutcome::Move(Stage::Waiting, Some(String::from("Scanning")))
+}
+
+/// Starts waiting despite the error if the force flag is set
+fn force(ctx: &Ctx, outcome: StepOutcome) -> StepOutcome {
+    match outcome {
+        StepOutcome::Error(_) | StepOutcome::Retry(_) if ctx.flags.1 => scanning(),
+        outcome => outcome,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Waiting
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Checks the availability of every item and takes the first available of its sizes. With the
+/// monitor flag sold out items are checked again after [`Monitor`]
+pub struct CheckAvailability;
+
+#[async_trait]
+impl CheckoutStep for CheckAvailability {
+    fn tier(&self) -> &'static str {
+        "E"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let mut cods = ctx.items.iter().map(|i| i.variant.id).collect::<Vec<u64>>();
+        cods.sort_unstable();
+        cods.dedup();
+
+        let mut sold_out = false;
+
+        for cod in cods {
+            let delay = ctx.delay(25..=30);
+
+            let variant = match ctx.client.product_data(cod, delay).await {
+                Ok(result) => match result.value {
+                    ResponseValue::Value(value) => match value.data.variant {
+                        Some(variant) => variant,
+                        None => return scheme("VRT", self.tier()),
+                    },
+                    _ => return scheme("V", self.tier()),
+                },
+                Err(err) => return failure(err, self.tier()),
+            };
+
+            for item in ctx.items.iter_mut().filter(|i| i.variant.id == cod) {
+                let sizes = if item.sizes.is_empty() {
+                    variant.sizes.values().collect::<Vec<&Size>>()
+                } else {
+                    item.sizes
+                        .iter()
+                        .filter_map(|id| variant.sizes.get(&id.to_string()))
+                        .collect()
+                };
+
+                if sizes.is_empty() {
+                    return error(ErrorKind::SizeNotFound, self.tier());
+                }
+
+                match sizes.into_iter().find(|s| !s.sold_out) {
+                    Some(size) => item.size = size.clone(),
+                    None => sold_out = true,
+                }
+            }
+        }
+
+        if !sold_out {
+            match ctx.guard.check_items(&ctx.items) {
+                Ok(()) => StepOutcome::Move(Stage::Process, None),
+                Err(reason) => StepOutcome::Failed(Some(reason)),
+            }
+        } else if ctx.flags.2 {
+            StepOutcome::Break(None)
+        } else {
+            error(ErrorKind::SoldOut, self.tier())
+        }
+    }
+}
+
+/// Waits for the monitor frequency before the next availability check
+pub struct Monitor;
+
+#[async_trait]
+impl CheckoutStep for Monitor {
+    fn tier(&self) -> &'static str {
+        "E/M"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        sleep(Duration::from_millis(ctx.flags.3)).await;
+
+        StepOutcome::Break(Some(String::from("Scanning")))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Process
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Adds every item to the cart. Items this checkout has added before (e.g. when it's resumed after
+/// a failed order) are not added again
+pub struct AddToCart;
+
+#[async_trait]
+impl CheckoutStep for AddToCart {
+    fn tier(&self) -> &'static str {
+        "F"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let lines = ctx
+            .items
+            .iter()
+            .map(|i| (i.variant.id, i.size.id, i.quantity))
+            .collect::<Vec<(u64, u64, u64)>>();
+        let wanted = lines
+            .iter()
+            .map(|&(_, size, count)| (size, count))
+            .collect::<Vec<(u64, u64)>>();
+
+        if ctx.added == wanted {
+            return StepOutcome::Continue;
+        }
+
+        let mut quantity = 0;
+
+        for (cod, size, count) in lines {
+            let delay = ctx.delay(25..=30);
+
+            match ctx.client.add_to_basket(cod, size, count, delay).await {
+                Ok(result) if result.state == -1 => return rejected(&result, self.tier()),
+                Ok(result) => match result.value {
+                    ResponseValue::Basket(data) => match data.basket_info {
+                        Some(basket_short) => quantity = basket_short.quantity,
+                        None => return scheme("BS", self.tier()),
+                    },
+                    _ => return scheme("V", self.tier()),
+                },
+                Err(err) => return failure(err, self.tier()),
+            }
+        }
+
+        let outcome = match quantity {
+            0 => error(
+                ErrorKind::Cart(String::from("Can't add product to cart")),
+                self.tier(),
+            ),
+            _ if quantity == ctx.items.iter().map(|i| i.quantity).sum::<u64>() => {
+                StepOutcome::Continue
+            }
+            _ if !ctx.flags.1 => error(
+                ErrorKind::Cart(String::from("Cart corrupted. Check it by yourself")),
+                self.tier(),
+            ),
+            _ => StepOutcome::Continue,
+        };
+
+        if matches!(outcome, StepOutcome::Continue) {
+            ctx.added = wanted;
+        }
+
+        outcome
+    }
+}
+
+/// Collects the final cart data
+pub struct CollectCart;
+
+#[async_trait]
+impl CheckoutStep for CollectCart {
+    fn tier(&self) -> &'static str {
+        "G"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let delay = ctx.delay(15..=25);
+
+        match ctx.client.basket_data(delay).await {
+            Ok(result) if result.state == 0 => match result.value {
+                ResponseValue::Value(value) => match value.data.basket {
+                    Some(data) => {
+                        ctx.cart = data;
+                        StepOutcome::Continue
+                    }
+                    None => scheme("B", self.tier()),
+                },
+                _ => scheme("V", self.tier()),
+            },
+            Ok(result) => rejected(&result, self.tier()),
+            Err(err) => failure(err, self.tier()),
+        }
+    }
+}
+
+/// Order form for the basket. Dates and stores are taken from the delivery way the basket has
+/// selected, a basket without it or with a date in an unknown format is a [`ErrorKind::Scheme`]
+/// error ("DW" and "DATE" parts)
+pub fn order_form(cart: &Basket) -> Result<Vec<(String, String)>, ErrorKind> {
+    let way = cart
+        .delivery_ways
+        .iter()
+        .find(|way| way.code == cart.delivery_way)
+        .ok_or_else(|| ErrorKind::Scheme(String::from("DW")))?;
+
+    let mut form = vec![
+        (
+            String::from("orderDetails.DeliveryPointId"),
+            cart.delivery_point.id.to_string(),
+        ),
+        (
+            String::from("orderDetails.DeliveryWay"),
+            cart.delivery_way.clone(),
+        ),
+        (String::from("orderDetails.DeliveryPrice"), String::new()),
+    ];
+
+    for (cid, c) in way.calendars.iter().enumerate() {
+        let date = c
+            .shipping_interval
+            .date()
+            .ok_or_else(|| ErrorKind::Scheme(String::from("DATE")))?;
+
+        form.push((
+            String::from("orderDetails.DeliveryDts.Index"),
+            cid.to_string(),
+        ));
+        form.push((
+            format!("orderDetails.DeliveryDts[{}].Date", cid),
+            date.format("%d.%m.%Y").to_string(),
+        ));
+        form.push((
+            format!("orderDetails.DeliveryDts[{}].IntervalId", cid),
+            c.shipping_interval.id.to_string(),
+        ));
+
+        for (sid, s) in c.store_ids.iter().enumerate() {
+            form.push((
+                format!("orderDetails.DeliveryDts[{}].StoreIds.Index", cid),
+                sid.to_string(),
+            ));
+            form.push((
+                format!("orderDetails.DeliveryDts[{}].StoreIds[{}]", cid, sid),
+                s.to_string(),
+            ));
+        }
+    }
+
+    form.push((
+        String::from("orderDetails.GooglePayToken"),
+        false.to_string(),
+    ));
+    form.push((
+        String::from("orderDetails.PaymentType.Id"),
+        cart.payment_type.id.to_string(),
+    ));
+    form.push((
+        String::from("orderDetails.MaskedCardId"),
+        cart.payment_type.card.clone(),
+    ));
+    form.push((String::from("orderDetails.SberPayPhone"), String::new()));
+    form.push((
+        String::from("orderDetails.AgreePublicOffert"),
+        true.to_string(),
+    ));
+    form.push((
+        String::from("orderDetails.TotalPrice"),
+        cart.total_price.to_string(),
+    ));
+
+    for (id, i) in cart.order_items.iter().enumerate() {
+        form.push((
+            String::from("orderDetails.UserBasketItems.Index"),
+            id.to_string(),
+        ));
+        form.push((
+            format!("orderDetails.UserBasketItems[{}].CharacteristicId", id),
+            i.to_string(),
+        ));
+        form.push((
+            format!("orderDetails.IncludeInOrder[{}]", id),
+            i.to_string(),
+        ));
+    }
+
+    Ok(form)
+}
+
+/// Submits the order. A 3-D Secure redirect ends the checkout right away, order and payment
+/// failure redirects are left to [`ConfirmOrder`] and [`PaymentError`]
+pub struct SubmitOrder;
+
+#[async_trait]
+impl CheckoutStep for SubmitOrder {
+    fn tier(&self) -> &'static str {
+        "H"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        if let Err(reason) = ctx.guard.check_total(ctx.cart.total_price) {
+            return StepOutcome::Failed(Some(reason));
+        }
+
+        let form = match order_form(&ctx.cart) {
+            Ok(form) => form,
+            Err(kind) => return error(kind, self.tier()),
+        };
+
+        let delay = ctx.delay(15..=20);
+
+        match ctx.client.submit_order(&form, delay).await {
+            Ok(result) if result.state == -1 => rejected(&result, self.tier()),
+            Ok(result) => match result.value {
+                ResponseValue::Order { url } => {
+                    if url.starts_with("https://beta.paywb.com") {
+                        ctx.end = Some((url, EndKind::UserAction));
+                        StepOutcome::Move(Stage::End, None)
+                    } else if url.ends_with("payment/fail") || url.contains("orderId") {
+                        ctx.order = Some(url);
+                        StepOutcome::Continue
+                    } else {
+                        scheme("URL", self.tier())
+                    }
+                }
+                _ => scheme("V", self.tier()),
+            },
+            Err(err) => failure(err, self.tier()),
+        }
+    }
+}
+
+/// Confirms the payment success of a placed order, skipped on payment failure
+pub struct ConfirmOrder;
+
+#[async_trait]
+impl CheckoutStep for ConfirmOrder {
+    fn tier(&self) -> &'static str {
+        "J"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let url = match ctx.order {
+            Some(ref url) if url.contains("orderId") => url.clone(),
+            _ => return StepOutcome::Continue,
+        };
+
+        let confirmed = match retrieve(&url, "?orderId=", "&paid") {
+            Some(oid) => {
+                let delay = ctx.delay(10..=15);
+
+                match ctx.client.order_confirmed(&oid, delay).await {
+                    Ok(result) => result.state == 0,
+                    Err(_) => false,
+                }
+            }
+            None => false,
+        };
+
+        ctx.end = Some((url, EndKind::Succeed(confirmed)));
+        StepOutcome::Move(Stage::End, Some(String::from("Sending embed")))
+    }
+}
+
+/// Parses the bank error of a failed payment
+pub struct PaymentError;
+
+#[async_trait]
+impl CheckoutStep for PaymentError {
+    fn tier(&self) -> &'static str {
+        "I"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let delay = ctx.delay(10..=15);
+
+        match ctx.client.payment_fail(delay).await {
+            Ok(desc) => {
+                ctx.end = Some((
+                    match desc {
+                        Some(desc) => desc,
+                        None => String::from("<Can't parse error>"),
+                    },
+                    EndKind::Failed,
+                ));
+                StepOutcome::Move(Stage::End, None)
+            }
+            Err(err) => failure(err, self.tier()),
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::{mem, time::Instant};
+
+use reqwest::{Client, StatusCode};
+use serde_json::{from_str, Value};
+
+use crate::{
+    error::{Error, ErrorKind},
+    log::{Level, Record, Tag},
+    misc::{client, request, retrieve, RequestMethod, Response},
+    models::ResponseResult,
+    site::Site,
+    transcript::{Exchange, Transcript},
+};
+
+static AUTH_COOKIE: &str = "WILDAUTHNEW_V3";
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Wildberries client
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct WildberriesClient {
+    client: Client,
+    site: Site,
+    tag: Tag,
+    /// Values redacted from the transcripts wherever they appear
+    secrets: Vec<String>,
+    /// Requests of the current tier, captured only when enabled
+    exchanges: Option<Vec<Exchange>>,
+}
+
+impl WildberriesClient {
+    /// Creates a client for `site` which goes through `proxy` (if any) and is authorized by
+    /// account `token`
+    pub fn new(site: Site, proxy: Option<String>, token: Option<&str>) -> WildberriesClient {
+        WildberriesClient {
+            client: match token {
+                Some(token) => client(
+                    proxy,
+                    Some(&[(
+                        String::from(AUTH_COOKIE),
+                        String::from(token),
+                        site.cookie_domain(),
+                    )]),
+                    site.is_secure(),
+                ),
+                None => client(proxy, None, site.is_secure()),
+            },
+            site,
+            tag: Tag::default(),
+            secrets: token.map(String::from).into_iter().collect(),
+            exchanges: None,
+        }
+    }
+
+    /// Keeps the requests of the current tier, so there is a transcript when it fails
+    pub fn capture(mut self, enabled: bool) -> WildberriesClient {
+        self.exchanges = if enabled { Some(Vec::new()) } else { None };
+        self
+    }
+
+    /// Tags the request logs with the task
+    pub fn task(mut self, uid: u64) -> WildberriesClient {
+        self.tag.task = Some(uid);
+        self
+    }
+
+    /// Tags the request logs with the tier of the running step
+    pub fn set_tier(&mut self, tier: &'static str) {
+        self.tag.tier = Some(tier);
+
+        if let Some(ref mut exchanges) = self.exchanges {
+            exchanges.clear();
+        }
+    }
+
+    /// Requests captured since the current tier has started, none if capturing is disabled
+    pub fn transcript(&mut self, error: &str) -> Option<Transcript> {
+        let exchanges = self.exchanges.as_mut()?;
+
+        Some(Transcript::new(
+            self.tag.task,
+            self.tag.tier.unwrap_or("-"),
+            error,
+            mem::take(exchanges),
+        ))
+    }
+
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    pub fn site(&self) -> &Site {
+        &self.site
+    }
+
+    async fn fetch(
+        &mut self,
+        path: &str,
+        method: RequestMethod<'_>,
+        referer: &str,
+        delay: u64,
+    ) -> Result<Response, Error> {
+        let url = self.site.url(path);
+        let (name, form) = match method {
+            RequestMethod::GET => ("GET", None),
+            RequestMethod::POST(form) => ("POST", form),
+        };
+        let record = Record::new(Level::Info, self.tag, "Response").request(name, &url);
+        let exchange = match self.exchanges {
+            Some(_) => Some(Exchange::new(name, &url, form, &self.secrets)),
+            None => None,
+        };
+
+        let start = Instant::now();
+        let resp = request(
+            &mut self.client,
+            &self.site,
+            &url,
+            method,
+            &self.site.url(referer),
+            delay,
+        )
+        .await;
+        // The delay is part of the request, not of the site latency
+        let latency = (start.elapsed().as_millis() as u64).saturating_sub(delay);
+
+        match resp {
+            Ok(resp) => {
+                let level = match resp.status.as_u16() {
+                    0..=399 => Level::Info,
+                    _ => Level::Warn,
+                };
+
+                Record { level, ..record }
+                    .response(Some(resp.status.as_u16()), latency)
+                    .log();
+
+                if let (Some(exchange), Some(exchanges)) = (exchange, self.exchanges.as_mut()) {
+                    exchanges.push(
+                        exchange
+                            .request_headers(&resp.request_headers, &self.secrets)
+                            .response(
+                                resp.status.as_u16(),
+                                &resp.headers,
+                                &resp.body,
+                                &self.secrets,
+                                latency,
+                            ),
+                    );
+                }
+
+                if resp.status.is_server_error() || resp.status == StatusCode::TOO_MANY_REQUESTS {
+                    return Err(ErrorKind::Http(resp.status.as_u16()).into());
+                }
+
+                Ok(resp)
+            }
+            Err(err) => {
+                let message = err.kind.to_string();
+
+                if let (Some(exchange), Some(exchanges)) = (exchange, self.exchanges.as_mut()) {
+                    exchanges.push(exchange.error(&message, latency));
+                }
+
+                Record {
+                    level: Level::Warn,
+                    message,
+                    ..record
+                }
+                .response(None, latency)
+                .log();
+
+                Err(err)
+            }
+        }
+    }
+
+    async fn result(
+        &mut self,
+        path: &str,
+        method: RequestMethod<'_>,
+        referer: &str,
+        delay: u64,
+    ) -> Result<ResponseResult, Error> {
+        let resp = self.fetch(path, method, referer, delay).await?;
+        parse(&resp)
+    }
+
+    /// Account info. Also used to check whether the account token is still valid
+    pub async fn personal_cabinet_data(&mut self, delay: u64) -> Result<ResponseResult, Error> {
+        self.result("/lk/personalcabinet/data", RequestMethod::GET, "/lk", delay)
+            .await
+    }
+
+    /// Sets user location cookies required by the basket
+    pub async fn user_location(&mut self, delay: u64) -> Result<ResponseResult, Error> {
+        self.result(
+            "/geo/getuserlocationinfo",
+            RequestMethod::POST(None),
+            &format!("/login?returnUrl={}/", self.site),
+            delay,
+        )
+        .await
+    }
+
+    pub async fn basket_data(&mut self, delay: u64) -> Result<ResponseResult, Error> {
+        self.result("/lk/basket/data", RequestMethod::GET, "/lk/basket", delay)
+            .await
+    }
+
+    /// Removes items (characteristic ids) from the basket
+    pub async fn basket_delete(
+        &mut self,
+        items: &[u64],
+        delay: u64,
+    ) -> Result<ResponseResult, Error> {
+        let form = items
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (format!("chrtIds[{}]", i), p.to_string()))
+            .collect();
+
+        self.result(
+            "/lk/basket/spa/delete",
+            RequestMethod::POST(Some(&form)),
+            "/lk/basket",
+            delay,
+        )
+        .await
+    }
+
+    /// Product card and selected variant (with sizes) by variant id (cod1S)
+    pub async fn product_data(&mut self, cod: u64, delay: u64) -> Result<ResponseResult, Error> {
+        let resp = self
+            .fetch(
+                &format!("/{}/product/data?targetUrl=XS", cod),
+                RequestMethod::GET,
+                &format!("/catalog/{}/detail.aspx?targetUrl=XS", cod),
+                delay,
+            )
+            .await?;
+
+        if resp.status == StatusCode::NOT_FOUND {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        parse(&resp)
+    }
+
+    pub async fn add_to_basket(
+        &mut self,
+        cod: u64,
+        size: u64,
+        quantity: u64,
+        delay: u64,
+    ) -> Result<ResponseResult, Error> {
+        self.result(
+            "/product/addtobasket",
+            RequestMethod::POST(Some(&vec![
+                (String::from("cod1S"), cod.to_string()),
+                (String::from("characteristicId"), size.to_string()),
+                (String::from("quantity"), quantity.to_string()),
+            ])),
+            &format!("/catalog/{}/detail.aspx?targetUrl=XS", cod),
+            delay,
+        )
+        .await
+    }
+
+    /// Submits order form. On success the value is `ResponseValue::Order` with a redirect url
+    pub async fn submit_order(
+        &mut self,
+        form: &Vec<(String, String)>,
+        delay: u64,
+    ) -> Result<ResponseResult, Error> {
+        self.result(
+            "/lk/basket/spa/submitorder",
+            RequestMethod::POST(Some(form)),
+            "/lk/basket",
+            delay,
+        )
+        .await
+    }
+
+    pub async fn order_confirmed(
+        &mut self,
+        order_id: &str,
+        delay: u64,
+    ) -> Result<ResponseResult, Error> {
+        self.result(
+            &format!("/lk/order/confirmed/data?orderId={}&paid=True", order_id),
+            RequestMethod::GET,
+            "/lk/basket",
+            delay,
+        )
+        .await
+    }
+
+    /// Payment error description parsed from the payment fail page
+    pub async fn payment_fail(&mut self, delay: u64) -> Result<Option<String>, Error> {
+        let resp = self
+            .fetch("/lk/payment/fail", RequestMethod::GET, "/lk/basket", delay)
+            .await?;
+
+        Ok(retrieve(
+            &resp.body,
+            r#"<p class="field-validation-error">"#,
+            "</p>",
+        ))
+    }
+
+    /// Raw JSON of any page, for checks of the response shapes
+    pub async fn json(&mut self, path: &str, referer: &str) -> Result<Value, Error> {
+        let resp = self.fetch(path, RequestMethod::GET, referer, 0).await?;
+
+        if resp.status == StatusCode::NOT_FOUND {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        from_str::<Value>(&resp.body).map_err(|err| {
+            ErrorKind::Response {
+                status: resp.status.as_u16(),
+                cause: err.to_string(),
+            }
+            .into()
+        })
+    }
+}
+
+fn parse(resp: &Response) -> Result<ResponseResult, Error> {
+    from_str::<ResponseResult>(&resp.body)
+        .map(|result| ResponseResult {
+            status: resp.status.as_u16(),
+            ..result
+        })
+        .map_err(|err| {
+            ErrorKind::Response {
+                status: resp.status.as_u16(),
+                cause: err.to_string(),
+            }
+            .into()
+        })
+}
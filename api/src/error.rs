@@ -0,0 +1,134 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Error
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// What went wrong. Codes of the kinds never change, so they can be counted and reported
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub enum ErrorKind {
+    /// Broken checkout flow or task state
+    Internal(String),
+    Timeout,
+    /// Connection error with its cause
+    Connection(String),
+    /// Server error or rate limit (HTTP status)
+    Http(u16),
+    /// Product doesn't exist
+    NotFound,
+    /// Body is not a result
+    Response {
+        status: u16,
+        cause: String,
+    },
+    /// Result misses a part the step relies on
+    Scheme(String),
+    /// Site refused the request with its `resultState`, message and HTTP status
+    Rejected {
+        state: i64,
+        message: Option<String>,
+        #[serde(default)]
+        status: u16,
+    },
+    TokenExpired,
+    SizeNotFound,
+    SoldOut,
+    /// Cart doesn't hold the items
+    Cart(String),
+}
+
+impl ErrorKind {
+    pub fn code(&self) -> u8 {
+        match *self {
+            ErrorKind::Internal(_) => 0,
+            ErrorKind::Timeout => 1,
+            ErrorKind::Connection(_) => 2,
+            ErrorKind::Http(_) => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Response { .. } => 5,
+            ErrorKind::Scheme(_) => 6,
+            ErrorKind::Rejected { .. } => 7,
+            ErrorKind::TokenExpired => 8,
+            ErrorKind::SizeNotFound => 9,
+            ErrorKind::SoldOut => 10,
+            ErrorKind::Cart(_) => 11,
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Internal(msg) | ErrorKind::Cart(msg) => write!(f, "{}", msg),
+            ErrorKind::Timeout => write!(f, "Timeout"),
+            ErrorKind::Connection(cause) => write!(f, "Connection Error: {}", cause),
+            ErrorKind::Http(status) => write!(f, "Server error {}", status),
+            ErrorKind::NotFound => write!(f, "Not found"),
+            ErrorKind::Response { .. } => write!(f, "Bad response"),
+            ErrorKind::Scheme(_) => write!(f, "Unknown scheme"),
+            ErrorKind::Rejected {
+                message: Some(msg), ..
+            } => write!(f, "{}", msg),
+            ErrorKind::Rejected { state, status, .. } => {
+                write!(f, "Rejected by the site ({}, HTTP {})", state, status)
+            }
+            ErrorKind::TokenExpired => write!(f, "Account token is expired"),
+            ErrorKind::SizeNotFound => write!(f, "Size not found"),
+            ErrorKind::SoldOut => write!(f, "Size is sold out"),
+        }
+    }
+}
+
+/// Error of a request or a checkout step
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Error {
+    /// Tier of the checkout step, empty outside of the checkout
+    pub tier: String,
+    pub kind: ErrorKind,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            tier: String::new(),
+            kind,
+        }
+    }
+
+    pub fn tier(mut self, tier: &str) -> Error {
+        self.tier = tier.to_string();
+        self
+    }
+
+    pub fn code(&self) -> u8 {
+        self.kind.code()
+    }
+
+    /// Timeouts, connection and server errors, which may pass on retry
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::Timeout | ErrorKind::Connection(_) | ErrorKind::Http(_)
+        )
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error::new(kind)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.kind, self.tier.as_str()) {
+            (_, "") => write!(f, "{}", self.kind),
+            (ErrorKind::Scheme(part), tier) => write!(f, "{} ({}/{})", self.kind, tier, part),
+            (_, tier) => write!(f, "{} ({})", self.kind, tier),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,7 +1,9 @@
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
+pub mod checkout;
+pub mod client;
+pub mod error;
+pub mod log;
+pub mod misc;
+pub mod models;
+pub mod schema;
+pub mod site;
+pub mod transcript;
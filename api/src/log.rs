@@ -0,0 +1,166 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Log records
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Level::Debug => "DEBUG",
+                Level::Info => "INFO",
+                Level::Warn => "WARN",
+                Level::Error => "ERROR",
+            }
+        )
+    }
+}
+
+/// What the records of a client are tagged with
+#[derive(Default, Copy, Clone, Debug)]
+pub struct Tag {
+    pub task: Option<u64>,
+    /// Tier of the running checkout step
+    pub tier: Option<&'static str>,
+}
+
+impl Tag {
+    pub fn task(uid: u64) -> Tag {
+        Tag {
+            task: Some(uid),
+            tier: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Record {
+    /// Unix time (ms)
+    pub time: u64,
+    pub level: Level,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<String>,
+    pub message: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Request duration (ms)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<u64>,
+}
+
+impl Record {
+    pub fn new(level: Level, tag: Tag, message: impl Into<String>) -> Record {
+        Record {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|dur| dur.as_millis() as u64)
+                .unwrap_or_default(),
+            level,
+            task: tag.task,
+            tier: tag.tier.map(String::from),
+            message: message.into(),
+            method: None,
+            url: None,
+            status: None,
+            latency: None,
+        }
+    }
+
+    pub fn request(mut self, method: &str, url: &str) -> Record {
+        self.method = Some(method.to_string());
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn response(mut self, status: Option<u16>, latency: u64) -> Record {
+        self.status = status;
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Writes the record to the sink
+    pub fn log(self) {
+        log(self)
+    }
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<5}",
+            Local
+                .timestamp_millis_opt(self.time as i64)
+                .single()
+                .map(|time| time.format("%H:%M:%S%.3f").to_string())
+                .unwrap_or_default(),
+            self.level
+        )?;
+
+        if let Some(task) = self.task {
+            write!(f, " #{}", task)?;
+        }
+        if let Some(ref tier) = self.tier {
+            write!(f, " [{}]", tier)?;
+        }
+
+        write!(f, " {}", self.message)?;
+
+        if let (Some(method), Some(url)) = (&self.method, &self.url) {
+            write!(f, " {} {}", method, url)?;
+        }
+        if let Some(status) = self.status {
+            write!(f, " -> {}", status)?;
+        }
+        if let Some(latency) = self.latency {
+            write!(f, " ({} ms)", latency)?;
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Sink
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+type Sink = Box<dyn Fn(Record) + Send + Sync>;
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+/// Sets where the records go, once. Without a sink the records are dropped, printing them is up
+/// to the binaries
+pub fn set_sink(sink: impl Fn(Record) + Send + Sync + 'static) -> bool {
+    SINK.set(Box::new(sink)).is_ok()
+}
+
+pub fn log(record: Record) {
+    if let Some(sink) = SINK.get() {
+        sink(record);
+    }
+}
@@ -5,34 +5,33 @@ use reqwest::{cookie::Jar, header::HeaderMap, Client, Proxy, StatusCode, Url, Ve
 use tokio::time::sleep;
 
 use crate::{
-    H_ACCEPT, H_ACCEPT_ENCODING, H_ACCEPT_LANGUAGE, H_CACHE_CONTROL, H_HOST, H_ORIGIN, H_PRAGMA,
-    H_SEC_FETCH_DEST, H_SEC_FETCH_MODE, H_SEC_FETCH_SITE, H_TE, H_USER_AGENT, H_X_REQUESTED_WITH,
-    H_X_SPA_VERSION,
+    error::{Error, ErrorKind},
+    site::Site,
 };
 
+#[cfg(target_os = "linux")]
+pub static H_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:91.0) Gecko/20100101 Firefox/91.0";
+#[cfg(not(target_os = "linux"))]
+pub static H_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:91.0) Gecko/20100101 Firefox/91.0";
+
+static H_ACCEPT: &str = "*/*";
+static H_ACCEPT_LANGUAGE: &str = "ru-RU,ru;q=0.5";
+static H_ACCEPT_ENCODING: &str = "gzip, deflate, br";
+static H_X_REQUESTED_WITH: &str = "XMLHttpRequest";
+static H_X_SPA_VERSION: &str = "8.0.4";
+static H_SEC_FETCH_DEST: &str = "empty";
+static H_SEC_FETCH_MODE: &str = "cors";
+static H_SEC_FETCH_SITE: &str = "same-origin";
+static H_PRAGMA: &str = "no-cache";
+static H_CACHE_CONTROL: &str = "no-cache";
+static H_TE: &str = "trailers";
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Requests
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Debug)]
-pub enum ResponseStatus {
-    Timeout,
-    ConnectionError,
-}
-
-impl ResponseStatus {
-    pub fn to_string(&self, tier: &str) -> String {
-        format!(
-            "{} ({})",
-            match &self {
-                ResponseStatus::Timeout => String::from("Timeout"),
-                ResponseStatus::ConnectionError => String::from("Connection Error"),
-            },
-            tier
-        )
-    }
-}
-
 pub enum ResponseMethod {
     GET,
     POST,
@@ -43,6 +42,8 @@ pub struct Response {
     pub version: Version,
     pub status: StatusCode,
 
+    /// Headers the request was sent with, except the ones added by the client (cookies)
+    pub request_headers: HeaderMap,
     pub headers: HeaderMap,
 
     pub body: String,
@@ -55,25 +56,22 @@ pub enum RequestMethod<'a> {
 
 pub async fn request<'a>(
     client: &mut Client,
+    site: &Site,
     url: &str,
     method: RequestMethod<'a>,
     referer: &str,
     delay: u64,
-) -> Result<Response, ResponseStatus> {
-    #[cfg(debug_assertions)]
-    println!("WAITING FOR GET: {}", url);
+) -> Result<Response, Error> {
     sleep(Duration::from_millis(delay)).await;
     let resp_method;
 
-    match match method {
+    let req = match match method {
         RequestMethod::GET => {
             resp_method = ResponseMethod::GET;
 
-            #[cfg(debug_assertions)]
-            println!("GET REQUEST ON: {}", url);
             client
                 .get(url)
-                .header("Host", H_HOST)
+                .header("Host", site.host())
                 .header("User-Agent", H_USER_AGENT)
                 .header("Accept", H_ACCEPT)
                 .header("Accept-Language", H_ACCEPT_LANGUAGE)
@@ -88,17 +86,14 @@ pub async fn request<'a>(
                 .header("Pragma", H_PRAGMA)
                 .header("Cache-Control", H_CACHE_CONTROL)
                 .header("TE", H_TE)
-                .send()
-                .await
+                .build()
         }
         RequestMethod::POST(form) => {
             resp_method = ResponseMethod::POST;
 
-            #[cfg(debug_assertions)]
-            println!("POST REQUEST ON: {}", url);
             let mut client = client
                 .post(url)
-                .header("Host", H_HOST)
+                .header("Host", site.host())
                 .header("User-Agent", H_USER_AGENT)
                 .header("Accept", H_ACCEPT)
                 .header("Accept-Language", H_ACCEPT_LANGUAGE)
@@ -107,7 +102,7 @@ pub async fn request<'a>(
                 .header("x-requested-with", H_X_REQUESTED_WITH)
                 .header("x-spa-version", H_X_SPA_VERSION)
                 .header("Content-Type", "application/x-www-form-urlencoded")
-                .header("Origin", H_ORIGIN)
+                .header("Origin", site.origin())
                 .header("DNT", 1)
                 .header("Sec-Fetch-Dest", H_SEC_FETCH_DEST)
                 .header("Sec-Fetch-Mode", H_SEC_FETCH_MODE)
@@ -121,72 +116,75 @@ pub async fn request<'a>(
                 None => client = client.header("Content-Length", "0"),
             }
 
-            client.send().await
+            client.build()
         }
     } {
+        Ok(req) => req,
+        Err(err) => return Err(failure(err)),
+    };
+    let request_headers = req.headers().clone();
+
+    match client.execute(req).await {
         Ok(resp) => {
             let mut result = Response {
                 method: resp_method,
                 version: resp.version(),
                 status: resp.status(),
+                request_headers,
                 headers: resp.headers().clone(),
                 body: String::new(),
             };
 
             match resp.text().await {
                 Ok(text) => result.body = text,
-                Err(err) => {
-                    return if err.is_timeout() {
-                        Err(ResponseStatus::Timeout)
-                    } else {
-                        Err(ResponseStatus::ConnectionError)
-                    }
-                }
+                Err(err) => return Err(failure(err)),
             }
 
             Ok(result)
         }
-        Err(err) => {
-            return if err.is_timeout() {
-                Err(ResponseStatus::Timeout)
-            } else {
-                Err(ResponseStatus::ConnectionError)
-            }
-        }
+        Err(err) => Err(failure(err)),
     }
 }
 
+fn failure(err: reqwest::Error) -> Error {
+    Error::new(if err.is_timeout() {
+        ErrorKind::Timeout
+    } else {
+        ErrorKind::Connection(err.to_string())
+    })
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Requests client
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-pub fn client(proxy: Option<String>, cookies: Option<&[(String, String, String)]>) -> Client {
+pub fn client(
+    proxy: Option<String>,
+    cookies: Option<&[(String, String, String)]>,
+    https_only: bool,
+) -> Client {
     let mut client = Client::builder()
         .tcp_keepalive(Some(Duration::from_secs(4)))
         .timeout(Duration::from_secs(8))
         .cookie_store(true)
         .user_agent(H_USER_AGENT)
         .gzip(true)
-        .https_only(true)
+        .https_only(https_only)
         .http1_title_case_headers();
 
-    match proxy {
-        Some(address) => client = client.proxy(Proxy::all(format!("https://{}", address)).unwrap()),
-        None => (),
+    if let Some(address) = proxy {
+        client = client.proxy(Proxy::all(format!("https://{}", address)).unwrap());
     }
 
-    match cookies {
-        Some(val) => {
-            let jar = Jar::default();
-            for i in val {
-                jar.add_cookie_str(
-                    &format!("{}={}; Domain={}", i.0, i.1, i.2),
-                    &format!("https://{}", i.2).parse::<Url>().unwrap(),
-                );
-            }
-            client = client.cookie_provider(Arc::new(jar))
+    if let Some(val) = cookies {
+        let jar = Jar::default();
+        for i in val {
+            jar.add_cookie_str(
+                &format!("{}={}; Domain={}", i.0, i.1, i.2),
+                &format!("https://{}", i.2).parse::<Url>().unwrap(),
+            );
         }
-        None => (),
+        client = client.cookie_provider(Arc::new(jar))
     }
 
     client.build().unwrap()
@@ -196,7 +194,7 @@ pub fn client(proxy: Option<String>, cookies: Option<&[(String, String, String)]
 // String tools
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-pub fn retrieve(text: &String, start: &str, end: &str) -> Option<String> {
+pub fn retrieve(text: &str, start: &str, end: &str) -> Option<String> {
     match text.find(start) {
         Some(mut i) => {
             i += start.len();
@@ -204,10 +202,7 @@ pub fn retrieve(text: &String, start: &str, end: &str) -> Option<String> {
             if end.is_empty() {
                 Some(text[i..].to_string())
             } else {
-                match text[i..].find(end) {
-                    Some(j) => Some(text[i..i + j].to_string()),
-                    None => None,
-                }
+                text[i..].find(end).map(|j| text[i..i + j].to_string())
             }
         }
         None => None,
@@ -0,0 +1,436 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::{read_to_string, write},
+    io,
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::{
+    client::WildberriesClient,
+    models::{ResponseResult, ResponseValue},
+    transcript::redact_sample,
+};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Samples
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Response of the site the checkout relies on
+#[derive(Copy, Clone, Debug)]
+pub enum Sample {
+    /// Product data of a variant (cod1S)
+    Product(u64),
+    /// Basket of the account
+    Basket,
+}
+
+impl Sample {
+    /// Name of the golden sample file (without `.json`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sample::Product(_) => "product",
+            Sample::Basket => "basket",
+        }
+    }
+
+    fn path(&self) -> (String, String) {
+        match *self {
+            Sample::Product(cod) => (
+                format!("/{}/product/data?targetUrl=XS", cod),
+                format!("/catalog/{}/detail.aspx?targetUrl=XS", cod),
+            ),
+            Sample::Basket => (String::from("/lk/basket/data"), String::from("/lk/basket")),
+        }
+    }
+
+    /// Problems of the response parsed into the models
+    fn validate(&self, value: &Value) -> Vec<String> {
+        let result = match serde_json::from_value::<ResponseResult>(value.clone()) {
+            Ok(result) => result,
+            Err(err) => return vec![format!("Not a result: {}", err)],
+        };
+        let mut errors = Vec::new();
+
+        if result.state != 0 {
+            errors.push(format!(
+                "resultState is {}{}",
+                result.state,
+                result
+                    .message()
+                    .map(|msg| format!(" ({})", msg))
+                    .unwrap_or_default()
+            ));
+        }
+
+        let data = match result.value {
+            ResponseValue::Value(value) => value.data,
+            ResponseValue::Message(_) if result.state != 0 => return errors,
+            other => {
+                errors.push(format!("Value is parsed as {}", variant(&other)));
+                return errors;
+            }
+        };
+
+        match self {
+            Sample::Product(_) => {
+                if data.product_card.is_none() {
+                    errors.push(String::from("productCard is missing"));
+                }
+                match data.variant {
+                    Some(variant) if variant.sizes.is_empty() => {
+                        errors.push(String::from("selectedNomenclature has no sizes"))
+                    }
+                    Some(_) => {}
+                    None => errors.push(String::from("selectedNomenclature is missing")),
+                }
+            }
+            Sample::Basket => {
+                if data.basket.is_none() {
+                    errors.push(String::from("basket is missing"));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+fn variant(value: &ResponseValue) -> &'static str {
+    match value {
+        ResponseValue::Message(_) => "Message",
+        ResponseValue::Value(_) => "Value",
+        ResponseValue::Order { .. } => "Order",
+        ResponseValue::Basket(_) => "Basket",
+        ResponseValue::None => "None",
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Shapes
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Field paths of a JSON value with their types. Array items and objects keyed by ids (like the
+/// sizes of a variant) are merged under `[]` and `{}`
+pub fn shape(value: &Value) -> BTreeMap<String, &'static str> {
+    let mut fields = BTreeMap::new();
+    walk(value, String::new(), &mut fields);
+    fields
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn walk(value: &Value, path: String, fields: &mut BTreeMap<String, &'static str>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    // Null says nothing about the type, the field is known to be there though
+    match fields.get(&path) {
+        Some(&known) if value.is_null() && !path.is_empty() => {
+            fields.insert(path.clone(), known);
+        }
+        _ => {
+            if !path.is_empty() {
+                fields.insert(path.clone(), kind(value));
+            }
+        }
+    }
+
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                walk(item, format!("{}[]", path), fields);
+            }
+        }
+        Value::Object(map)
+            if !map.is_empty() && map.keys().all(|k| k.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            for item in map.values() {
+                walk(item, join("{}"), fields);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                walk(item, join(key), fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Difference of a response from its golden sample
+#[derive(PartialEq, Debug)]
+pub enum Change {
+    Missing(String, &'static str),
+    Added(String, &'static str),
+    Retyped(String, &'static str, &'static str),
+    /// Missing and added fields of the same object and type
+    Renamed(String, String),
+}
+
+impl Change {
+    /// Fields the models can't rely on anymore, added ones are harmless
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, Change::Added(..))
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Missing(path, kind) => write!(f, "- {} ({})", path, kind),
+            Change::Added(path, kind) => write!(f, "+ {} ({})", path, kind),
+            Change::Retyped(path, from, to) => write!(f, "~ {} ({} -> {})", path, from, to),
+            Change::Renamed(from, to) => write!(f, "> {} -> {}", from, to),
+        }
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rfind('.').map(|i| &path[..i]).unwrap_or_default()
+}
+
+/// Whether the live response has items at every `[]`/`{}` of the path, empty arrays and maps
+/// can't miss fields of their items
+fn has_items(path: &str, live: &BTreeMap<String, &'static str>) -> bool {
+    path.match_indices(['[', '{'])
+        .all(|(i, _)| live.contains_key(&path[..i + 2]))
+}
+
+/// Changes of the live response against the golden sample
+pub fn diff(golden: &Value, live: &Value) -> Vec<Change> {
+    let (golden, live) = (shape(golden), shape(live));
+    let compatible = |a: &str, b: &str| a == b || a == "null" || b == "null";
+
+    let mut missing = golden
+        .iter()
+        .filter(|(path, _)| !live.contains_key(*path) && has_items(path, &live))
+        .map(|(path, &kind)| (path.clone(), kind))
+        .collect::<Vec<(String, &'static str)>>();
+    let mut added = live
+        .iter()
+        .filter(|(path, _)| !golden.contains_key(*path))
+        .map(|(path, &kind)| (path.clone(), kind))
+        .collect::<Vec<(String, &'static str)>>();
+
+    // Fields of a missing object are only reported with it
+    let nested = |fields: &[(String, &str)], path: &str| {
+        fields.iter().any(|(other, _)| {
+            path.starts_with(other.as_str()) && path[other.len()..].starts_with(['.', '['])
+        })
+    };
+    let missing_roots = missing.clone();
+    missing.retain(|(path, _)| !nested(&missing_roots, path));
+    let added_roots = added.clone();
+    added.retain(|(path, _)| !nested(&added_roots, path));
+
+    let mut changes = Vec::new();
+
+    missing.retain(|(path, kind)| {
+        match added
+            .iter()
+            .position(|(other, other_kind)| parent(other) == parent(path) && other_kind == kind)
+        {
+            Some(i) => {
+                changes.push(Change::Renamed(path.clone(), added.remove(i).0));
+                false
+            }
+            None => true,
+        }
+    });
+
+    changes.extend(
+        missing
+            .into_iter()
+            .map(|(path, kind)| Change::Missing(path, kind)),
+    );
+    changes.extend(
+        golden
+            .iter()
+            .filter_map(|(path, &from)| match live.get(path) {
+                Some(&to) if !compatible(from, to) => Some(Change::Retyped(path.clone(), from, to)),
+                _ => None,
+            }),
+    );
+    changes.extend(
+        added
+            .into_iter()
+            .map(|(path, kind)| Change::Added(path, kind)),
+    );
+
+    changes
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Schema check
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Result of checking a response
+pub struct Report {
+    pub sample: Sample,
+    /// Fetch, parse and model errors
+    pub errors: Vec<String>,
+    pub changes: Vec<Change>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && !self.changes.iter().any(Change::is_breaking)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {}",
+            self.sample.name(),
+            if self.is_ok() { "OK" } else { "FAILED" }
+        )?;
+
+        for err in self.errors.iter() {
+            writeln!(f, "  ! {}", err)?;
+        }
+        for change in self.changes.iter() {
+            writeln!(f, "  {}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn golden_file(dir: &Path, sample: Sample) -> std::path::PathBuf {
+    dir.join(format!("{}.json", sample.name()))
+}
+
+/// Fetches the sample and checks it against the models and the golden sample in `dir`
+pub async fn check(client: &mut WildberriesClient, sample: Sample, dir: &Path) -> Report {
+    let mut report = Report {
+        sample,
+        errors: Vec::new(),
+        changes: Vec::new(),
+    };
+
+    let (path, referer) = sample.path();
+    let mut live = match client.json(&path, &referer).await {
+        Ok(live) => live,
+        Err(err) => {
+            report.errors.push(format!("Can't fetch: {}", err));
+            return report;
+        }
+    };
+
+    report.errors.extend(sample.validate(&live));
+    // Golden samples are stored redacted, so are the fields compared with them
+    redact_sample(&mut live);
+
+    let file = golden_file(dir, sample);
+    match read_to_string(&file)
+        .map_err(|err| err.to_string())
+        .and_then(|text| serde_json::from_str::<Value>(&text).map_err(|err| err.to_string()))
+    {
+        Ok(golden) => report.changes = diff(&golden, &live),
+        Err(err) => report
+            .errors
+            .push(format!("Can't read {}: {}", file.display(), err)),
+    }
+
+    report
+}
+
+/// Fetches the sample and stores it as the golden one in `dir`, without the secrets and the
+/// personal data of the account
+pub async fn update(client: &mut WildberriesClient, sample: Sample, dir: &Path) -> io::Result<()> {
+    let (path, referer) = sample.path();
+    let mut live = client
+        .json(&path, &referer)
+        .await
+        .map_err(io::Error::other)?;
+    redact_sample(&mut live);
+
+    write(
+        golden_file(dir, sample),
+        serde_json::to_string_pretty(&live)? + "\n",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{diff, shape, Change};
+
+    #[test]
+    fn shapes() {
+        let fields = shape(&json!({
+            "value": {
+                "sizes": { "1": { "id": 1 }, "2": { "id": 2, "name": null } },
+                "items": [{ "id": 1 }, { "name": "M" }],
+            }
+        }));
+
+        assert_eq!(
+            fields.into_iter().collect::<Vec<(String, &str)>>(),
+            vec![
+                (String::from("value"), "object"),
+                (String::from("value.items"), "array"),
+                (String::from("value.items[]"), "object"),
+                (String::from("value.items[].id"), "number"),
+                (String::from("value.items[].name"), "string"),
+                (String::from("value.sizes"), "object"),
+                (String::from("value.sizes.{}"), "object"),
+                (String::from("value.sizes.{}.id"), "number"),
+                (String::from("value.sizes.{}.name"), "null"),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes() {
+        let golden = json!({
+            "resultState": 0,
+            "value": {
+                "data": { "productCard": { "goodsName": "A" }, "price": 1 },
+                "items": [{ "id": 1 }],
+                "gone": { "a": 1, "b": [1] },
+            }
+        });
+        let live = json!({
+            "resultState": 0,
+            "value": {
+                "data": { "productCard": { "name": "A" }, "price": "1", "new": null },
+                "items": [],
+            }
+        });
+
+        assert_eq!(
+            diff(&golden, &live),
+            vec![
+                Change::Renamed(
+                    String::from("value.data.productCard.goodsName"),
+                    String::from("value.data.productCard.name")
+                ),
+                Change::Missing(String::from("value.gone"), "object"),
+                Change::Retyped(String::from("value.data.price"), "number", "string"),
+                Change::Added(String::from("value.data.new"), "null"),
+            ]
+        );
+        assert!(diff(&golden, &golden).is_empty());
+    }
+}
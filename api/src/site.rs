@@ -0,0 +1,113 @@
+use std::{
+    env,
+    fmt::{Display, Formatter},
+};
+
+use reqwest::Url;
+
+pub static DEFAULT_SITE_URL: &str = "https://www.wildberries.ru";
+pub static SITE_URL_ENV: &str = "SDP_SITE_URL";
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Site
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Base url all site endpoints are resolved against (production or a local stand-in)
+#[derive(PartialEq, Clone, Debug)]
+pub struct Site {
+    base: Url,
+}
+
+impl Site {
+    /// Parses base url. Only `http`/`https` urls with a host are accepted
+    pub fn new(url: &str) -> Option<Site> {
+        match Url::parse(url.trim().trim_end_matches('/')) {
+            Ok(base)
+                if (base.scheme() == "http" || base.scheme() == "https") && base.has_host() =>
+            {
+                Some(Site { base })
+            }
+            _ => None,
+        }
+    }
+
+    /// Site from `SDP_SITE_URL` environment variable
+    pub fn from_env() -> Option<Site> {
+        match env::var(SITE_URL_ENV) {
+            Ok(url) => Site::new(&url),
+            Err(_) => None,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.origin(), path)
+    }
+
+    pub fn host(&self) -> String {
+        match self.base.port() {
+            Some(port) => format!("{}:{}", self.base.host_str().unwrap_or_default(), port),
+            None => self.base.host_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    pub fn origin(&self) -> String {
+        self.base.origin().ascii_serialization()
+    }
+
+    /// Domain the auth cookie is set for (host without `www.`)
+    pub fn cookie_domain(&self) -> String {
+        let host = self.base.host_str().unwrap_or_default();
+        host.strip_prefix("www.").unwrap_or(host).to_string()
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.base.scheme() == "https"
+    }
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Site::new(DEFAULT_SITE_URL).unwrap()
+    }
+}
+
+impl Display for Site {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.origin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Site;
+
+    #[test]
+    fn default_site() {
+        let site = Site::default();
+
+        assert_eq!(site.host(), "www.wildberries.ru");
+        assert_eq!(site.cookie_domain(), "wildberries.ru");
+        assert_eq!(
+            site.url("/lk/basket"),
+            "https://www.wildberries.ru/lk/basket"
+        );
+        assert!(site.is_secure());
+    }
+
+    #[test]
+    fn local_site() {
+        let site = Site::new("http://127.0.0.1:8080/").unwrap();
+
+        assert_eq!(site.host(), "127.0.0.1:8080");
+        assert_eq!(site.origin(), "http://127.0.0.1:8080");
+        assert_eq!(site.cookie_domain(), "127.0.0.1");
+        assert!(!site.is_secure());
+    }
+
+    #[test]
+    fn invalid_site() {
+        assert!(Site::new("").is_none());
+        assert!(Site::new("wildberries.ru").is_none());
+        assert!(Site::new("ftp://wildberries.ru").is_none());
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{header::HeaderMap, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+static REDACTED: &str = "[redacted]";
+/// Names (lowercase) of the headers whose values are never kept, cookies keep their names
+static SECRET_HEADERS: [&str; 2] = ["authorization", "proxy-authorization"];
+/// Parts (lowercase) of the query, form and JSON keys whose values are never kept
+static SECRET_KEYS: [&str; 5] = ["auth", "token", "password", "session", "secret"];
+/// Parts (lowercase) of the JSON keys of personal data, kept in transcripts but not in samples
+static PERSONAL_KEYS: [&str; 4] = ["address", "phone", "email", "card"];
+/// Response bodies are cut to this size (bytes)
+const MAX_BODY: usize = 64 * 1024;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Transcripts
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Request made by a checkout step and what the site returned, with the secrets redacted
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Exchange {
+    /// Unix time (ms)
+    pub time: u64,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub form: Vec<(String, String)>,
+
+    pub status: Option<u16>,
+    pub response_headers: Vec<(String, String)>,
+    pub body: String,
+    /// Timeout or connection error, there is no response then
+    pub error: Option<String>,
+    /// Request duration (ms)
+    pub latency: u64,
+}
+
+/// Requests of the tier which stopped the checkout with an error
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Transcript {
+    /// Unix time (ms)
+    pub time: u64,
+    pub task: Option<u64>,
+    pub tier: String,
+    pub error: String,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Exchange {
+    /// Exchange with `secrets` (e.g. the account token) and the values of secret headers, query
+    /// and form fields redacted
+    pub fn new(
+        method: &str,
+        url: &str,
+        form: Option<&Vec<(String, String)>>,
+        secrets: &[String],
+    ) -> Exchange {
+        Exchange {
+            time: now(),
+            method: method.to_string(),
+            url: hide(&redact_url(url), secrets),
+            request_headers: Vec::new(),
+            form: form
+                .map(|form| {
+                    form.iter()
+                        .map(|(key, value)| (key.clone(), hide(&redact_value(key, value), secrets)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            status: None,
+            response_headers: Vec::new(),
+            body: String::new(),
+            error: None,
+            latency: 0,
+        }
+    }
+
+    pub fn request_headers(mut self, headers: &HeaderMap, secrets: &[String]) -> Exchange {
+        self.request_headers = redact_headers(headers, secrets);
+        self
+    }
+
+    pub fn response(
+        mut self,
+        status: u16,
+        headers: &HeaderMap,
+        body: &str,
+        secrets: &[String],
+        latency: u64,
+    ) -> Exchange {
+        self.status = Some(status);
+        self.response_headers = redact_headers(headers, secrets);
+        self.body = hide(&redact_body(body), secrets);
+        self.latency = latency;
+        self
+    }
+
+    pub fn error(mut self, error: &str, latency: u64) -> Exchange {
+        self.error = Some(error.to_string());
+        self.latency = latency;
+        self
+    }
+}
+
+impl Transcript {
+    pub fn new(task: Option<u64>, tier: &str, error: &str, exchanges: Vec<Exchange>) -> Transcript {
+        Transcript {
+            time: now(),
+            task,
+            tier: tier.to_string(),
+            error: error.to_string(),
+            exchanges,
+        }
+    }
+}
+
+impl Display for Transcript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Tier {}: {}", self.tier, self.error)?;
+
+        for ex in self.exchanges.iter() {
+            writeln!(f)?;
+            writeln!(f, "> {} {}", ex.method, ex.url)?;
+            for (name, value) in ex.request_headers.iter() {
+                writeln!(f, "> {}: {}", name, value)?;
+            }
+            if !ex.form.is_empty() {
+                writeln!(
+                    f,
+                    "> {}",
+                    ex.form
+                        .iter()
+                        .map(|(key, value)| format!("{}={}", key, value))
+                        .collect::<Vec<String>>()
+                        .join("&")
+                )?;
+            }
+
+            match (ex.status, &ex.error) {
+                (_, Some(err)) => writeln!(f, "< {} ({} ms)", err, ex.latency)?,
+                (Some(status), None) => writeln!(f, "< {} ({} ms)", status, ex.latency)?,
+                (None, None) => {}
+            }
+            for (name, value) in ex.response_headers.iter() {
+                writeln!(f, "< {}: {}", name, value)?;
+            }
+            if !ex.body.is_empty() {
+                writeln!(f, "<")?;
+                writeln!(f, "{}", ex.body)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Redaction
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+fn is_secret(key: &str) -> bool {
+    let key = key.to_lowercase();
+    SECRET_KEYS.iter().any(|part| key.contains(part))
+}
+
+fn redact_value(key: &str, value: &str) -> String {
+    if is_secret(key) && !value.is_empty() {
+        REDACTED.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Cookie names are kept, the values are not
+fn redact_cookies(value: &str, set: bool) -> String {
+    let mut parts = value.split(';').map(str::trim);
+    let redact = |pair: &str| match pair.split_once('=') {
+        Some((name, _)) => format!("{}={}", name, REDACTED),
+        None => pair.to_string(),
+    };
+
+    if set {
+        // Only the first pair is the cookie, the rest are its attributes
+        parts
+            .next()
+            .map(redact)
+            .into_iter()
+            .chain(parts.map(String::from))
+            .collect::<Vec<String>>()
+            .join("; ")
+    } else {
+        parts.map(redact).collect::<Vec<String>>().join("; ")
+    }
+}
+
+fn redact_headers(headers: &HeaderMap, secrets: &[String]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str();
+            let value = String::from_utf8_lossy(value.as_bytes());
+            let value = match name {
+                "cookie" => redact_cookies(&value, false),
+                "set-cookie" => redact_cookies(&value, true),
+                name if SECRET_HEADERS.contains(&name) => REDACTED.to_string(),
+                _ => hide(&value, secrets),
+            };
+
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) if parsed.query_pairs().any(|(key, _)| is_secret(&key)) => {
+            let pairs = parsed
+                .query_pairs()
+                .map(|(key, value)| (key.to_string(), redact_value(&key, &value)))
+                .collect::<Vec<(String, String)>>();
+            parsed.query_pairs_mut().clear().extend_pairs(pairs);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+fn redact_keys(value: &mut Value, hidden: &impl Fn(&str) -> bool) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(_) | Value::Number(_) if hidden(key) => {
+                        *value = Value::String(REDACTED.to_string())
+                    }
+                    value => redact_keys(value, hidden),
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| redact_keys(value, hidden)),
+        _ => {}
+    }
+}
+
+fn redact_json(value: &mut Value) {
+    redact_keys(value, &is_secret)
+}
+
+/// Redacts the secrets and the personal data (addresses, phones, cards) of a response which is
+/// stored outside of the application, like the golden samples of the schema check
+pub fn redact_sample(value: &mut Value) {
+    redact_keys(value, &|key: &str| {
+        let lower = key.to_lowercase();
+        is_secret(key) || PERSONAL_KEYS.iter().any(|part| lower.contains(part))
+    })
+}
+
+/// JSON bodies have the values of secret keys redacted, any body is cut to [`MAX_BODY`]
+fn redact_body(body: &str) -> String {
+    let mut body = match serde_json::from_str::<Value>(body) {
+        Ok(mut value) => {
+            redact_json(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    };
+
+    if body.len() > MAX_BODY {
+        let mut end = MAX_BODY;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        body.truncate(end);
+        body.push('…');
+    }
+
+    body
+}
+
+/// Replaces the known secrets wherever they appear
+fn hide(text: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| {
+            text.replace(secret.as_str(), REDACTED)
+        })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use serde_json::json;
+
+    use super::{redact_body, redact_headers, redact_sample, Exchange};
+
+    #[test]
+    fn headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "cookie",
+            HeaderValue::from_static("a=1; WILDAUTHNEW_V3=TOKEN"),
+        );
+        headers.insert(
+            "set-cookie",
+            HeaderValue::from_static("__wbl=cityId=0; path=/; HttpOnly"),
+        );
+        headers.insert("authorization", HeaderValue::from_static("Bearer TOKEN"));
+        headers.insert("referer", HeaderValue::from_static("/lk?x=TOKEN"));
+
+        assert_eq!(
+            redact_headers(&headers, &[String::from("TOKEN")]),
+            vec![
+                (
+                    String::from("cookie"),
+                    String::from("a=[redacted]; WILDAUTHNEW_V3=[redacted]")
+                ),
+                (
+                    String::from("set-cookie"),
+                    String::from("__wbl=[redacted]; path=/; HttpOnly")
+                ),
+                (String::from("authorization"), String::from("[redacted]")),
+                (String::from("referer"), String::from("/lk?x=[redacted]")),
+            ]
+        );
+    }
+
+    #[test]
+    fn query_and_form() {
+        let form = vec![
+            (String::from("orderDetails.token"), String::from("abc")),
+            (String::from("cod1S"), String::from("42")),
+        ];
+        let exchange = Exchange::new(
+            "POST",
+            "https://www.wildberries.ru/lk/order?accessToken=abc&targetUrl=XS",
+            Some(&form),
+            &[],
+        );
+
+        assert_eq!(
+            exchange.url,
+            "https://www.wildberries.ru/lk/order?accessToken=%5Bredacted%5D&targetUrl=XS"
+        );
+        assert_eq!(exchange.form[0].1, "[redacted]");
+        assert_eq!(exchange.form[1].1, "42");
+    }
+
+    #[test]
+    fn body() {
+        assert_eq!(
+            redact_body(r#"{"value":{"user":{"id":1,"sessionId":"abc","phone":"7900"}}}"#),
+            r#"{"value":{"user":{"id":1,"phone":"7900","sessionId":"[redacted]"}}}"#
+        );
+        assert_eq!(redact_body("<html>token</html>"), "<html>token</html>");
+        assert!(redact_body(&"x".repeat(100_000)).len() < 70_000);
+    }
+
+    #[test]
+    fn sample() {
+        let mut value = json!({
+            "deliveryPoint": { "address": "Москва", "kladrId": 77 },
+            "paymentType": { "id": "bankCard", "bankCardId": "card-1" },
+            "productCard": { "goodsName": "A" },
+            "user": { "phone": "7900", "sessionId": "abc" },
+        });
+        redact_sample(&mut value);
+
+        assert_eq!(
+            value,
+            json!({
+                "deliveryPoint": { "address": "[redacted]", "kladrId": 77 },
+                "paymentType": { "id": "bankCard", "bankCardId": "[redacted]" },
+                "productCard": { "goodsName": "A" },
+                "user": { "phone": "[redacted]", "sessionId": "[redacted]" },
+            })
+        );
+    }
+}
@@ -0,0 +1,804 @@
+use std::{
+    net::TcpListener,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use api::{
+    checkout::{
+        order_form, AddToCart, CheckAvailability, Checkout, CheckoutStep, ClearCart, CollectCart,
+        Ctx, EndKind, FetchCart, Flow, Item, PriceGuard, RetryPolicy, Stage, StepOutcome,
+        SubmitOrder, TokenCheck, MAX_BACKOFF,
+    },
+    client::WildberriesClient,
+    error::ErrorKind,
+    models::{Basket, ResponseValue},
+    site::Site,
+};
+use async_trait::async_trait;
+use mock::{scenario::Scenario, serve};
+
+/// Starts the mock and prepares a context for `lines` of (cod, size index, quantity)
+async fn ctx_items(
+    scenario: Scenario,
+    lines: &[(u64, usize, u64)],
+    flags: (bool, bool, bool, u64),
+) -> Ctx {
+    let (addr, server) = serve(&([127, 0, 0, 1], 0).into(), scenario.clone()).unwrap();
+    tokio::spawn(server);
+
+    let site = Site::new(&format!("http://{}", addr)).unwrap();
+    let mut client = WildberriesClient::new(site.clone(), None, None);
+    let mut items = Vec::new();
+
+    for &(cod, size, quantity) in lines {
+        let (card, variant) = match client.product_data(cod, 0).await.unwrap().value {
+            ResponseValue::Value(value) => (
+                value.data.product_card.unwrap(),
+                value.data.variant.unwrap(),
+            ),
+            value => panic!("unexpected product value: {:?}", value),
+        };
+        let size = scenario.product(cod).unwrap().sizes[size].id.to_string();
+        let size = variant.sizes[&size].clone();
+
+        items.push(Item::new(card, variant, size, quantity));
+    }
+
+    Ctx::new(
+        WildberriesClient::new(site, None, Some("MOCKTOKEN")),
+        items,
+        flags,
+    )
+}
+
+/// Starts the mock and prepares a context for its product with the first size
+async fn ctx(scenario: Scenario, flags: (bool, bool, bool, u64)) -> Ctx {
+    let cod = scenario.product.cod;
+    ctx_items(scenario, &[(cod, 0, 1)], flags).await
+}
+
+async fn ctx_expired() -> Ctx {
+    ctx(
+        Scenario::builtin("expired-token").unwrap(),
+        (false, false, false, 0),
+    )
+    .await
+}
+
+/// Context for steps which make no requests
+fn offline_ctx(site: Site) -> Ctx {
+    let card = serde_json::from_str(r#"{"goodsName":"Offline"}"#).unwrap();
+    let variant = serde_json::from_str(r#"{"isSoldOut":false,"cod1S":1,"sizes":{}}"#).unwrap();
+    let size = serde_json::from_str(
+        r#"{"characteristicId":1,"sizeName":"M","price":1,"priceWithSale":1,"quantity":1,"isSoldOut":false}"#,
+    )
+    .unwrap();
+
+    Ctx::new(
+        WildberriesClient::new(site, None, Some("MOCKTOKEN")),
+        vec![Item::new(card, variant, size, 1)],
+        (false, false, false, 0),
+    )
+}
+
+/// Completes the checkout, so the flow needs no notifications
+struct Done;
+
+#[async_trait]
+impl CheckoutStep for Done {
+    fn tier(&self) -> &'static str {
+        "Done"
+    }
+
+    async fn run(&self, _ctx: &mut Ctx) -> StepOutcome {
+        StepOutcome::Complete(None)
+    }
+}
+
+/// Drives the regular checkout until it stops
+async fn checkout(ctx: Ctx) -> (Checkout, StepOutcome) {
+    let mut checkout = Checkout::new(
+        Flow::checkout().stage(Stage::End, vec![Box::new(Done)]),
+        ctx,
+    );
+
+    for _ in 0..32 {
+        match checkout.step().await {
+            outcome @ StepOutcome::Error(_)
+            | outcome @ StepOutcome::Complete(_)
+            | outcome @ StepOutcome::Failed(_) => return (checkout, outcome),
+            _ => {}
+        }
+    }
+
+    panic!("checkout didn't stop");
+}
+
+#[tokio::test]
+async fn token_check() {
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0)).await;
+    assert!(matches!(
+        TokenCheck.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+
+    let mut ctx = ctx_expired().await;
+    match TokenCheck.run(&mut ctx).await {
+        StepOutcome::Error(err) => {
+            assert_eq!(err.kind, ErrorKind::TokenExpired);
+            assert_eq!(err.code(), 8);
+            assert_eq!(err.to_string(), "Account token is expired (A)");
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[tokio::test]
+async fn cart_errors_are_forced() {
+    let mut ctx = ctx_expired().await;
+    assert!(matches!(
+        FetchCart.run(&mut ctx).await,
+        StepOutcome::Error(_)
+    ));
+
+    ctx.flags.1 = true;
+    assert!(matches!(
+        FetchCart.run(&mut ctx).await,
+        StepOutcome::Move(Stage::Waiting, Some(_))
+    ));
+}
+
+#[tokio::test]
+async fn clear_cart() {
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0)).await;
+    let (cod, size) = (ctx.items[0].variant.id, ctx.items[0].size.id);
+
+    ctx.client.add_to_basket(cod, size, 1, 0).await.unwrap();
+    assert!(matches!(
+        FetchCart.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+    assert_eq!(ctx.cart.order_items, vec![size]);
+
+    assert!(matches!(
+        ClearCart.run(&mut ctx).await,
+        StepOutcome::Move(Stage::Waiting, Some(_))
+    ));
+    assert!(matches!(
+        FetchCart.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+    assert!(ctx.cart.order_items.is_empty());
+}
+
+#[tokio::test]
+async fn sold_out() {
+    let mut ctx = ctx(
+        Scenario::builtin("sold-out").unwrap(),
+        (false, false, false, 0),
+    )
+    .await;
+    match CheckAvailability.run(&mut ctx).await {
+        StepOutcome::Error(err) => assert_eq!(err.kind, ErrorKind::SoldOut),
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+
+    ctx.flags.2 = true;
+    assert!(matches!(
+        CheckAvailability.run(&mut ctx).await,
+        StepOutcome::Break(None)
+    ));
+}
+
+#[tokio::test]
+async fn site_message_is_kept() {
+    let mut ctx = ctx(
+        Scenario::builtin("sold-out").unwrap(),
+        (false, false, false, 0),
+    )
+    .await;
+
+    match AddToCart.run(&mut ctx).await {
+        StepOutcome::Error(err) => {
+            assert_eq!(
+                err.kind,
+                ErrorKind::Rejected {
+                    state: -1,
+                    message: Some(String::from("Товар закончился")),
+                    status: 200,
+                }
+            );
+            assert_eq!(err.to_string(), "Товар закончился (F)");
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[tokio::test]
+async fn size_priority() {
+    let mut scenario = Scenario::default();
+    let (m, l) = (scenario.product.sizes[0].id, scenario.product.sizes[1].id);
+
+    let mut task = ctx(scenario.clone(), (false, false, false, 0)).await;
+    task.items[0].sizes = vec![l, m];
+    assert!(matches!(
+        CheckAvailability.run(&mut task).await,
+        StepOutcome::Move(Stage::Process, None)
+    ));
+    assert_eq!(task.items[0].size.id, l);
+
+    // The first available one is taken
+    scenario.product.sizes[1].quantity = 0;
+
+    let mut task = ctx(scenario.clone(), (false, false, false, 0)).await;
+    task.items[0].sizes = vec![l, m];
+    assert!(matches!(
+        CheckAvailability.run(&mut task).await,
+        StepOutcome::Move(Stage::Process, None)
+    ));
+    assert_eq!(task.items[0].size.id, m);
+
+    task.items[0].sizes = vec![l];
+    assert!(matches!(
+        CheckAvailability.run(&mut task).await,
+        StepOutcome::Error(_)
+    ));
+
+    // Any size
+    scenario.product.sizes[0].quantity = 0;
+    scenario.product.sizes[1].quantity = 10;
+
+    let mut task = ctx(scenario, (false, false, false, 0)).await;
+    task.items[0].sizes.clear();
+    let (checkout, outcome) = checkout(task).await;
+
+    assert!(matches!(outcome, StepOutcome::Complete(None)));
+    assert_eq!(checkout.ctx.cart.order_items, vec![l]);
+}
+
+#[tokio::test]
+async fn price_guard() {
+    // 4990 RUB, 3990 RUB with the 20% discount
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0))
+        .await
+        .guard(PriceGuard {
+            max_price: Some(3990),
+            min_discount: Some(20),
+        });
+    assert!(matches!(
+        CheckAvailability.run(&mut ctx).await,
+        StepOutcome::Move(Stage::Process, None)
+    ));
+
+    ctx.guard.min_discount = Some(25);
+    match CheckAvailability.run(&mut ctx).await {
+        StepOutcome::Failed(Some(reason)) => assert!(reason.starts_with("Discount 20%")),
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+
+    ctx.guard.min_discount = None;
+    ctx.items[0].quantity = 2;
+    assert!(matches!(
+        CheckAvailability.run(&mut ctx).await,
+        StepOutcome::Failed(Some(_))
+    ));
+
+    // The price is checked again on the cart
+    ctx.items[0].quantity = 1;
+    for step in [&AddToCart as &dyn CheckoutStep, &CollectCart] {
+        assert!(matches!(step.run(&mut ctx).await, StepOutcome::Continue));
+    }
+
+    ctx.guard.max_price = Some(3000);
+    match SubmitOrder.run(&mut ctx).await {
+        StepOutcome::Failed(Some(reason)) => {
+            assert_eq!(reason, "Price 3990 RUB is above the max of 3000 RUB")
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert!(ctx.order.is_none());
+}
+
+#[tokio::test]
+async fn process() {
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0)).await;
+
+    assert!(matches!(
+        CheckAvailability.run(&mut ctx).await,
+        StepOutcome::Move(Stage::Process, None)
+    ));
+    assert!(matches!(
+        AddToCart.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+    assert!(matches!(
+        CollectCart.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+    assert_eq!(ctx.cart.order_items, vec![ctx.items[0].size.id]);
+    assert!(matches!(
+        SubmitOrder.run(&mut ctx).await,
+        StepOutcome::Continue
+    ));
+    assert!(ctx.order.as_ref().unwrap().contains("orderId="));
+}
+
+#[tokio::test]
+async fn bundle() {
+    let mut scenario = Scenario::default();
+    let mut other = scenario.product.clone();
+    other.cod = 10000002;
+    other.sizes[0].id = 20000011;
+    other.sizes[1].id = 20000012;
+    scenario.products.push(other);
+
+    let lines = [(10000001, 0, 2), (10000001, 1, 1), (10000002, 1, 3)];
+    let ctx = ctx_items(scenario.clone(), &lines, (false, false, false, 0)).await;
+    let (checkout, outcome) = checkout(ctx).await;
+
+    assert!(matches!(outcome, StepOutcome::Complete(None)));
+    assert_eq!(
+        checkout.ctx.cart.order_items,
+        vec![20000001, 20000002, 20000012]
+    );
+    assert_eq!(
+        checkout.ctx.cart.total_price,
+        6 * scenario.product.sizes[0].sale_price
+    );
+
+    // A sold out line stops the whole order
+    scenario.products[0].sizes[1].quantity = 0;
+    let mut ctx = ctx_items(scenario, &lines, (false, false, false, 0)).await;
+    assert!(matches!(
+        CheckAvailability.run(&mut ctx).await,
+        StepOutcome::Error(_)
+    ));
+}
+
+#[tokio::test]
+async fn success() {
+    let (checkout, outcome) =
+        checkout(ctx(Scenario::default(), (false, false, false, 0)).await).await;
+
+    assert!(matches!(outcome, StepOutcome::Complete(None)));
+    assert_eq!(checkout.stage(), Stage::End);
+    assert!(matches!(
+        checkout.ctx.end,
+        Some((ref url, EndKind::Succeed(true))) if url.contains("orderId=1")
+    ));
+}
+
+#[tokio::test]
+async fn unconfirmed() {
+    let (checkout, _) = checkout(
+        ctx(
+            Scenario::builtin("unconfirmed").unwrap(),
+            (false, false, false, 0),
+        )
+        .await,
+    )
+    .await;
+
+    assert!(matches!(
+        checkout.ctx.end,
+        Some((_, EndKind::Succeed(false)))
+    ));
+}
+
+#[tokio::test]
+async fn secure() {
+    let (checkout, _) = checkout(
+        ctx(
+            Scenario::builtin("secure").unwrap(),
+            (false, false, false, 0),
+        )
+        .await,
+    )
+    .await;
+
+    assert!(matches!(
+        checkout.ctx.end,
+        Some((ref url, EndKind::UserAction)) if url.starts_with("https://beta.paywb.com")
+    ));
+}
+
+#[tokio::test]
+async fn bank_failure() {
+    let scenario = Scenario::builtin("bank-failure").unwrap();
+    let (checkout, _) = checkout(ctx(scenario.clone(), (false, false, false, 0)).await).await;
+
+    assert!(matches!(
+        checkout.ctx.end,
+        Some((ref desc, EndKind::Failed)) if desc == &scenario.bank_error
+    ));
+}
+
+#[tokio::test]
+async fn expired_token() {
+    let (checkout, outcome) = checkout(ctx_expired().await).await;
+
+    assert!(matches!(outcome, StepOutcome::Error(_)));
+    assert_eq!(checkout.stage(), Stage::Warmup);
+    assert!(checkout.transcript().is_none());
+}
+
+#[tokio::test]
+async fn failed_tier_is_captured() {
+    let mut ctx = ctx_expired().await;
+    ctx.client = WildberriesClient::new(ctx.client.site().clone(), None, Some("MOCKTOKEN"))
+        .task(7)
+        .capture(true);
+
+    let (checkout, outcome) = checkout(ctx).await;
+    assert!(matches!(outcome, StepOutcome::Error(_)));
+
+    let transcript = checkout.transcript().unwrap();
+    assert_eq!(transcript.task, Some(7));
+    assert_eq!(transcript.tier, "A");
+    assert_eq!(transcript.error, "Account token is expired (A)");
+
+    // Only the requests of the failed tier are kept
+    assert_eq!(transcript.exchanges.len(), 1);
+    let exchange = &transcript.exchanges[0];
+    assert_eq!(exchange.method, "GET");
+    assert!(exchange.url.ends_with("/lk/personalcabinet/data"));
+    assert_eq!(exchange.status, Some(200));
+    assert!(exchange.body.contains("-1"));
+    assert!(!exchange.request_headers.is_empty());
+    assert!(!transcript.to_string().contains("MOCKTOKEN"));
+}
+
+#[tokio::test]
+async fn restock_is_monitored() {
+    let mut scenario = Scenario::default();
+    scenario.product.sizes[0].available_after = 4;
+
+    let (checkout, outcome) = checkout(ctx(scenario, (false, false, true, 10)).await).await;
+
+    assert!(matches!(outcome, StepOutcome::Complete(None)));
+    assert!(matches!(
+        checkout.ctx.end,
+        Some((_, EndKind::Succeed(true)))
+    ));
+}
+
+#[tokio::test]
+async fn schedule() {
+    let drop = SystemTime::now() + Duration::from_millis(1500);
+    let ctx = ctx(Scenario::default(), (false, false, false, 0))
+        .await
+        .schedule(drop, Duration::from_millis(1000));
+    let mut checkout = Checkout::new(
+        Flow::checkout().stage(Stage::End, vec![Box::new(Done)]),
+        ctx,
+    );
+
+    let mut waits = Vec::new();
+    loop {
+        match checkout.step().await {
+            StepOutcome::Wait(msg) => waits.push((checkout.stage(), msg)),
+            StepOutcome::Move(Stage::Warmup, _) => {
+                // Warmup starts within the lead time
+                let left = drop.duration_since(SystemTime::now()).unwrap();
+                assert!(left <= Duration::from_millis(1000));
+            }
+            StepOutcome::Complete(_) => break,
+            outcome => assert!(!matches!(outcome, StepOutcome::Error(_))),
+        }
+    }
+
+    assert!(SystemTime::now() >= drop);
+    assert!(matches!(
+        waits[0],
+        (Stage::Scheduled, ref msg) if msg.starts_with("Starts in 00:00:0")
+    ));
+    assert!(waits.iter().any(|(stage, _)| *stage == Stage::Waiting));
+}
+
+#[test]
+fn retry_backoff() {
+    let policy = RetryPolicy {
+        attempts: 8,
+        backoff: 100,
+        jitter: 0,
+    };
+
+    assert_eq!(policy.delay(1), 100);
+    assert_eq!(policy.delay(3), 400);
+    assert_eq!(policy.delay(64), MAX_BACKOFF);
+
+    let policy = RetryPolicy {
+        jitter: 50,
+        ..policy
+    };
+    assert!((100..=150).contains(&policy.delay(1)));
+
+    assert_eq!(
+        Flow::checkout().policy(SubmitOrder.tier()),
+        RetryPolicy::none()
+    );
+    assert_eq!(
+        Flow::checkout().policy(TokenCheck.tier()),
+        RetryPolicy::default()
+    );
+}
+
+#[tokio::test]
+async fn connection_errors_are_retried() {
+    // Nothing listens on the port once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut checkout = Checkout::new(
+        Flow::checkout().retry(RetryPolicy {
+            attempts: 2,
+            backoff: 1,
+            jitter: 0,
+        }),
+        offline_ctx(Site::new(&format!("http://{}", addr)).unwrap()),
+    );
+
+    for stage in [Stage::Scheduled, Stage::Warmup] {
+        assert!(matches!(
+            checkout.step().await,
+            StepOutcome::Move(s, None) if s == stage
+        ));
+    }
+    for attempt in 1..=2 {
+        assert!(matches!(checkout.step().await, StepOutcome::Retry(_)));
+        assert_eq!(checkout.attempt(), (attempt, 2));
+    }
+    match checkout.step().await {
+        StepOutcome::Error(err) => {
+            assert!(matches!(err.kind, ErrorKind::Connection(_)));
+            assert_eq!(err.tier, TokenCheck.tier());
+            // The cause is shown along with the kind
+            assert!(err.to_string().len() > "Connection Error: ".len());
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert_eq!(checkout.attempt(), (0, 2));
+}
+
+/// Adds a placeholder price to the cart
+struct Fill;
+
+#[async_trait]
+impl CheckoutStep for Fill {
+    fn tier(&self) -> &'static str {
+        "Fill"
+    }
+
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        ctx.cart.total_price += 100;
+        StepOutcome::Continue
+    }
+}
+
+/// Moves to the process stage
+struct Proceed;
+
+#[async_trait]
+impl CheckoutStep for Proceed {
+    fn tier(&self) -> &'static str {
+        "Proceed"
+    }
+
+    async fn run(&self, _ctx: &mut Ctx) -> StepOutcome {
+        StepOutcome::Move(Stage::Process, None)
+    }
+}
+
+/// Fails on the first run
+struct Flaky(AtomicBool);
+
+#[async_trait]
+impl CheckoutStep for Flaky {
+    fn tier(&self) -> &'static str {
+        "Flaky"
+    }
+
+    async fn run(&self, _ctx: &mut Ctx) -> StepOutcome {
+        if self.0.swap(false, Ordering::SeqCst) {
+            StepOutcome::Error(ErrorKind::Internal(String::from("Flaky")).into())
+        } else {
+            StepOutcome::Complete(None)
+        }
+    }
+}
+
+#[tokio::test]
+async fn resume() {
+    let mut checkout = Checkout::new(
+        Flow::new()
+            .stage(Stage::Warmup, vec![Box::new(Fill), Box::new(Proceed)])
+            .stage(
+                Stage::Process,
+                vec![Box::new(Fill), Box::new(Flaky(AtomicBool::new(true)))],
+            ),
+        offline_ctx(Site::default()),
+    );
+
+    for _ in 0..4 {
+        assert!(!matches!(checkout.step().await, StepOutcome::Error(_)));
+    }
+    assert!(matches!(checkout.step().await, StepOutcome::Error(_)));
+    assert_eq!(checkout.ctx.cart.total_price, 200);
+
+    // The failed step runs again with the cart kept
+    checkout.resume(Stage::Process);
+    assert!(matches!(checkout.step().await, StepOutcome::Complete(None)));
+    assert_eq!(checkout.ctx.cart.total_price, 200);
+
+    // Another stage starts over
+    checkout.resume(Stage::Warmup);
+    assert!(matches!(checkout.step().await, StepOutcome::Continue));
+    assert_eq!(checkout.ctx.cart.total_price, 300);
+}
+
+#[test]
+fn item_round_trip() {
+    let item = offline_ctx(Site::default())
+        .items
+        .remove(0)
+        .sizes(Vec::new());
+    let restored: Item = serde_json::from_str(&serde_json::to_string(&item).unwrap()).unwrap();
+
+    assert_eq!(restored.card.name, item.card.name);
+    assert_eq!(restored.variant.id, item.variant.id);
+    assert_eq!(restored.size.name, "M");
+    assert!(restored.sizes.is_empty());
+}
+
+/// Basket of the fixture, with "self" selected out of the "courier" and "self" delivery ways
+fn fixture_basket() -> Basket {
+    let text = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/basket.json"
+    ))
+    .unwrap();
+
+    match serde_json::from_str::<api::models::ResponseResult>(&text)
+        .unwrap()
+        .value
+    {
+        ResponseValue::Value(value) => value.data.basket.unwrap(),
+        value => panic!("unexpected basket value: {:?}", value),
+    }
+}
+
+#[test]
+fn order_form_uses_selected_delivery_way() {
+    let form = order_form(&fixture_basket()).unwrap();
+    let field = |name: &str| {
+        form.iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<&str>>()
+    };
+
+    assert_eq!(field("orderDetails.DeliveryWay"), vec!["self"]);
+    assert_eq!(field("orderDetails.DeliveryDts.Index"), vec!["0", "1"]);
+    assert_eq!(
+        field("orderDetails.DeliveryDts[0].Date"),
+        vec!["05.01.2027"]
+    );
+    assert_eq!(
+        field("orderDetails.DeliveryDts[1].Date"),
+        vec!["20.10.2026"]
+    );
+    assert_eq!(field("orderDetails.DeliveryDts[1].IntervalId"), vec!["2"]);
+    assert_eq!(
+        field("orderDetails.DeliveryDts[0].StoreIds[1]"),
+        vec!["507"]
+    );
+    assert_eq!(
+        field("orderDetails.DeliveryDts[1].StoreIds[0]"),
+        vec!["686"]
+    );
+    assert_eq!(field("orderDetails.TotalPrice"), vec!["7980"]);
+    assert_eq!(field("orderDetails.IncludeInOrder[1]"), vec!["20000003"]);
+    // Dates of the courier way are not sent
+    assert!(!form.iter().any(|(_, value)| value == "31.12.2026"));
+}
+
+#[test]
+fn order_form_errors() {
+    let mut cart = fixture_basket();
+    cart.delivery_way = String::from("postamat");
+    assert_eq!(
+        order_form(&cart),
+        Err(ErrorKind::Scheme(String::from("DW")))
+    );
+
+    let cart = Basket::default();
+    assert_eq!(
+        order_form(&cart),
+        Err(ErrorKind::Scheme(String::from("DW")))
+    );
+
+    let mut cart = fixture_basket();
+    cart.delivery_ways[1].calendars[1]
+        .shipping_interval
+        .delivery_date = String::from("2026-10-20");
+    assert_eq!(
+        order_form(&cart),
+        Err(ErrorKind::Scheme(String::from("DATE")))
+    );
+}
+
+/// A malformed cart fails the task instead of panicking
+#[tokio::test]
+async fn submit_malformed_cart() {
+    let mut ctx = offline_ctx(Site::default());
+    ctx.cart = fixture_basket();
+    ctx.cart.delivery_ways.clear();
+
+    match SubmitOrder.run(&mut ctx).await {
+        StepOutcome::Error(err) => {
+            assert_eq!(err.kind, ErrorKind::Scheme(String::from("DW")));
+            assert_eq!(err.to_string(), "Unknown scheme (H/DW)");
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert!(ctx.order.is_none());
+}
+
+/// Resuming after a failed submit doesn't add the collected items to the cart again
+#[tokio::test]
+async fn resume_after_submit_failure() {
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0)).await;
+
+    for step in [
+        &CheckAvailability as &dyn CheckoutStep,
+        &AddToCart,
+        &CollectCart,
+    ] {
+        assert!(!matches!(
+            step.run(&mut ctx).await,
+            StepOutcome::Error(_) | StepOutcome::Failed(_)
+        ));
+    }
+    ctx.guard.max_price = Some(3000);
+    assert!(matches!(
+        SubmitOrder.run(&mut ctx).await,
+        StepOutcome::Failed(Some(_))
+    ));
+    assert_eq!(ctx.cart.order_items.len(), 1);
+
+    // The waiting stage starts over and enters the process from its first step
+    ctx.guard.max_price = None;
+    let mut checkout = Checkout::new(Flow::checkout(), ctx);
+    checkout.resume(Stage::Waiting);
+
+    loop {
+        match checkout.step().await {
+            StepOutcome::Move(Stage::End, _) => break,
+            outcome @ StepOutcome::Error(_) | outcome @ StepOutcome::Failed(_) => {
+                panic!("unexpected outcome: {:?}", outcome)
+            }
+            _ => {}
+        }
+    }
+    assert!(checkout.ctx.order.as_ref().unwrap().contains("orderId="));
+}
+
+#[tokio::test]
+async fn size_in_cart_on_warmup() {
+    let mut ctx = ctx(Scenario::default(), (false, false, false, 0)).await;
+    let (cod, size) = (ctx.items[0].variant.id, ctx.items[0].size.id);
+    ctx.client.add_to_basket(cod, size, 1, 0).await.unwrap();
+
+    // The size is removed with the rest of the cart and added again
+    let (checkout, outcome) = checkout(ctx).await;
+
+    assert!(
+        matches!(outcome, StepOutcome::Complete(None)),
+        "{:?}",
+        outcome
+    );
+    assert_eq!(checkout.ctx.added, vec![(size, 1)]);
+    assert!(checkout.ctx.order.as_ref().unwrap().contains("orderId="));
+}
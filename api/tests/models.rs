@@ -0,0 +1,194 @@
+use std::{fs::read_to_string, path::Path};
+
+use api::models::{ResponseResult, ResponseValue, ShippingInterval, SizeTag};
+use chrono::NaiveDate;
+
+/// Responses captured from the site, kept in `tests/fixtures`
+fn fixture(name: &str) -> ResponseResult {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name));
+    let text = read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+    serde_json::from_str(&text).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+fn tag(id: u64, name: &str, quantity: u64) -> SizeTag {
+    SizeTag {
+        id,
+        name: name.to_string(),
+        quantity,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Response values
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn personal_cabinet_is_value() {
+    let result = fixture("personal_cabinet");
+    assert_eq!(result.state, 0);
+
+    match result.value {
+        ResponseValue::Value(value) => {
+            let user = value.user.unwrap();
+            assert_eq!(user.phone, 79001234567);
+            assert_eq!(user.first_name, "Иван");
+            assert!(value.data.basket_info.unwrap().is_auth);
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn product_is_value() {
+    match fixture("product").value {
+        ResponseValue::Value(value) => {
+            assert_eq!(value.data.product_card.unwrap().name, "Кроссовки");
+
+            let variant = value.data.variant.unwrap();
+            assert_eq!(variant.id, 10000001);
+            assert_eq!(variant.name.as_deref(), Some("белый"));
+            assert_eq!(variant.sizes["20000001"].sale_price, 3990);
+            assert!(variant.sizes["20000002"].sold_out);
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn basket_is_value() {
+    match fixture("basket").value {
+        ResponseValue::Value(value) => {
+            let basket = value.data.basket.unwrap();
+
+            assert_eq!(basket.delivery_way, "self");
+            assert_eq!(
+                basket
+                    .delivery_ways
+                    .iter()
+                    .map(|way| way.code.as_str())
+                    .collect::<Vec<&str>>(),
+                vec!["courier", "self"]
+            );
+            assert_eq!(
+                basket.delivery_ways[1].calendars[0].store_ids,
+                vec![117986, 507]
+            );
+            assert_eq!(basket.delivery_point.id, 7700000000000);
+            assert_eq!(basket.payment_type.card, "[redacted]");
+            assert_eq!(basket.order_items, vec![20000001, 20000003]);
+            assert_eq!(basket.total_price, 7980);
+            assert_eq!(value.data.basket_info.unwrap().quantity, 2);
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+/// Data without the `data` wrapper is a basket, not a value
+#[test]
+fn add_to_basket_is_basket() {
+    match fixture("add_to_basket").value {
+        ResponseValue::Basket(data) => {
+            assert_eq!(data.basket_info.unwrap().quantity, 1);
+            assert!(data.basket.is_none());
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+/// An order url must not be swallowed by the variants tried before it
+#[test]
+fn submit_order_is_order() {
+    for name in ["submit_order", "submit_order_3ds"] {
+        match fixture(name).value {
+            ResponseValue::Order { url } => assert!(url.contains("orderId=1"), "{}", url),
+            value => panic!("{}: unexpected value: {:?}", name, value),
+        }
+    }
+}
+
+#[test]
+fn rejected_is_message() {
+    let result = fixture("rejected");
+
+    assert_eq!(result.state, -1);
+    assert!(matches!(result.value, ResponseValue::Message(_)));
+    assert_eq!(result.message().as_deref(), Some("Товар закончился"));
+}
+
+#[test]
+fn missing_value_is_none() {
+    let result = fixture("basket_delete");
+
+    assert_eq!(result.state, 0);
+    assert!(matches!(result.value, ResponseValue::None));
+    assert_eq!(result.message(), None);
+}
+
+#[test]
+fn capitalized_keys_are_accepted() {
+    let result = fixture("legacy");
+    assert_eq!(result.state, 0);
+
+    match result.value {
+        ResponseValue::Value(value) => {
+            let info = value.data.basket_info.unwrap();
+            assert!(!info.is_auth);
+            assert_eq!(info.events_count, 3);
+        }
+        value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Sizes and delivery dates
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn sizes_tags_follow_size_ids() {
+    let variant = match fixture("product").value {
+        ResponseValue::Value(value) => value.data.variant.unwrap(),
+        value => panic!("unexpected value: {:?}", value),
+    };
+
+    assert_eq!(
+        variant.sizes_tags(),
+        vec![tag(20000001, "M", 7), tag(20000002, "L", 0)]
+    );
+}
+
+#[test]
+fn size_tag_display() {
+    assert_eq!(tag(1, "M", 7).to_string(), r#"Size "M" (Q: 7)"#);
+    assert_eq!(tag(2, "L", 0).to_string(), r#"Size "L" (Sold Out)"#);
+}
+
+#[test]
+fn delivery_dates() {
+    let date = |text: &str| {
+        ShippingInterval {
+            id: 1,
+            delivery_date: text.to_string(),
+        }
+        .date()
+    };
+
+    assert_eq!(date("1/5/2027"), NaiveDate::from_ymd_opt(2027, 1, 5));
+    assert_eq!(date("10/20/2026"), NaiveDate::from_ymd_opt(2026, 10, 20));
+    assert_eq!(date("01/05/2027"), NaiveDate::from_ymd_opt(2027, 1, 5));
+    assert_eq!(date("2027-01-05"), None);
+    assert_eq!(date("13/1/2027"), None);
+    assert_eq!(date(""), None);
+
+    let basket = match fixture("basket").value {
+        ResponseValue::Value(value) => value.data.basket.unwrap(),
+        value => panic!("unexpected value: {:?}", value),
+    };
+    for way in basket.delivery_ways {
+        for calendar in way.calendars {
+            assert!(calendar.shipping_interval.date().is_some());
+        }
+    }
+}
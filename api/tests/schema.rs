@@ -0,0 +1,75 @@
+use std::{
+    fs::{create_dir_all, read_to_string, write},
+    path::Path,
+};
+
+use api::{
+    client::WildberriesClient,
+    schema::{check, Change, Sample},
+    site::Site,
+};
+use mock::{scenario::Scenario, serve};
+
+/// Responses of the site the models are tested with are the golden samples
+static GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn client() -> (WildberriesClient, u64) {
+    let scenario = Scenario::default();
+    let cod = scenario.product.cod;
+    let (addr, server) = serve(&([127, 0, 0, 1], 0).into(), scenario).unwrap();
+    tokio::spawn(server);
+
+    let site = Site::new(&format!("http://{}", addr)).unwrap();
+    (WildberriesClient::new(site, None, Some("MOCKTOKEN")), cod)
+}
+
+#[tokio::test]
+async fn mock_matches_golden_samples() {
+    let (mut client, cod) = client();
+
+    for sample in [Sample::Product(cod), Sample::Basket] {
+        let report = check(&mut client, sample, Path::new(GOLDEN)).await;
+        assert!(report.is_ok(), "{}", report);
+        assert!(report.changes.is_empty(), "{}", report);
+    }
+}
+
+#[tokio::test]
+async fn drift_is_reported() {
+    let (mut client, cod) = client();
+
+    // Golden sample of a site which named the card differently and had prices as strings
+    let dir = std::env::temp_dir().join(format!("sdp-schema-{}", std::process::id()));
+    create_dir_all(&dir).unwrap();
+    write(
+        dir.join("product.json"),
+        read_to_string(Path::new(GOLDEN).join("product.json"))
+            .unwrap()
+            .replace("goodsName", "name")
+            .replace("\"price\": 4990", "\"price\": \"4990\""),
+    )
+    .unwrap();
+
+    let report = check(&mut client, Sample::Product(cod), &dir).await;
+
+    assert!(!report.is_ok());
+    assert!(report.errors.is_empty(), "{}", report);
+    assert_eq!(
+        report.changes,
+        vec![
+            Change::Renamed(
+                String::from("value.data.productCard.name"),
+                String::from("value.data.productCard.goodsName")
+            ),
+            Change::Retyped(
+                String::from("value.data.selectedNomenclature.sizes.{}.price"),
+                "string",
+                "number"
+            ),
+        ]
+    );
+
+    let report = check(&mut client, Sample::Basket, &dir).await;
+    assert!(!report.is_ok());
+    assert!(report.errors[0].starts_with("Can't read"), "{}", report);
+}
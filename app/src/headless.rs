@@ -0,0 +1,227 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{write, File},
+    path::Path,
+    sync::Arc,
+};
+
+use iced_futures::futures::{stream, StreamExt};
+use serde_json::to_writer;
+use tokio::runtime::Builder;
+
+use crate::{
+    layout::load_file,
+    logic::{
+        activation::Activation,
+        embeds::Embeds,
+        models::Settings,
+        notify::Notifiers,
+        task::{Task, TaskProgress},
+    },
+    views::tabs::{
+        accounts::Account,
+        proxy::{Proxy, ProxyMode},
+    },
+    ACCOUNTS_FILE, LICENSE_FILE, PROXY_FILE, SETTINGS_FILE, TASKS_FILE,
+};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Headless mode
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+const USAGE: &str = "Usage: app --headless [--tasks <file>] [--json]
+
+Runs the unfinished tasks of the task file (tasks.json by default) without a window and prints
+their progress to stdout, as text or as JSON lines with --json";
+
+/// Arguments of the headless mode
+pub struct Args {
+    /// Task file, in the format of the tasks file saved by the application
+    tasks: String,
+    json: bool,
+}
+
+impl Args {
+    /// Arguments of the headless mode, none if the application is started with a window
+    pub fn parse(args: impl Iterator<Item = String>) -> Option<Result<Args, String>> {
+        let mut args = args.skip(1).peekable();
+
+        match args.peek() {
+            Some(arg) if arg == "--headless" => args.next(),
+            _ => return None,
+        };
+
+        let mut parsed = Args {
+            tasks: String::from(TASKS_FILE),
+            json: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tasks" => match args.next() {
+                    Some(path) => parsed.tasks = path,
+                    None => return Some(Err(String::from("--tasks requires a file"))),
+                },
+                "--json" => parsed.json = true,
+                "--help" | "-h" => return Some(Err(String::from(USAGE))),
+                other => return Some(Err(format!("Unknown argument {}\n\n{}", other, USAGE))),
+            }
+        }
+
+        Some(Ok(parsed))
+    }
+}
+
+/// Runs the tasks to the end, the exit code is 0 if every one of them has completed
+pub fn run(args: Args) -> i32 {
+    let mut settings = Settings::default();
+    let mut accounts: Vec<Account> = Vec::new();
+    let mut proxies: Vec<Proxy> = Vec::new();
+    let mut tasks: Vec<Task> = Vec::new();
+
+    load_file(SETTINGS_FILE, &mut settings);
+    load_file(ACCOUNTS_FILE, &mut accounts);
+    load_file(PROXY_FILE, &mut proxies);
+    load_file(&args.tasks, &mut tasks);
+    settings.embeds = Arc::new(Embeds::load());
+
+    // Account tokens might have been renewed since the tasks were saved
+    let tokens = accounts
+        .into_iter()
+        .map(|a| (a.phone, a.token))
+        .collect::<HashMap<String, String>>();
+
+    // Proxies might have been turned off or removed since then, the tasks get the active ones in
+    // the proxy mode of the settings
+    let active = proxies
+        .into_iter()
+        .filter(|p| p.active)
+        .map(|p| p.address)
+        .collect::<Vec<String>>();
+    let taken = tasks
+        .iter()
+        .filter_map(|task| task.proxy.clone())
+        .collect::<Vec<String>>();
+    let mut unused = active
+        .iter()
+        .filter(|p| !taken.contains(p))
+        .cloned()
+        .collect::<VecDeque<String>>();
+    let mut cycle = active.iter().cycle();
+
+    for task in tasks.iter_mut() {
+        task.restore(settings.site(), Notifiers::new(&settings), true);
+
+        if let Some(token) = tokens.get(&task.account.0) {
+            task.account.1 = token.clone();
+        }
+
+        if !task.progress.is_running() {
+            continue;
+        }
+
+        let kept = task.proxy.take().filter(|p| active.contains(p));
+        task.proxy = match settings.proxy_mode {
+            ProxyMode::Off => None,
+            _ if kept.is_some() => kept,
+            ProxyMode::Moderate => unused.pop_front(),
+            ProxyMode::Repeat | ProxyMode::Strict => cycle.next().cloned(),
+        };
+
+        if settings.proxy_mode == ProxyMode::Strict && task.proxy.is_none() {
+            eprintln!(
+                "No active proxy for task #{} in the strict proxy mode",
+                task.uid
+            );
+            task.progress = TaskProgress::Idle;
+        }
+    }
+
+    // Finished tasks stay in the file but aren't run again
+    if !tasks.iter().any(|task| task.progress.is_running()) {
+        eprintln!("No tasks to run in {}", args.tasks);
+        return 1;
+    }
+
+    let runtime = match Builder::new_multi_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Can't start the runtime: {}", err);
+            return 1;
+        }
+    };
+
+    runtime.block_on(async {
+        let token = Activation::load_token(LICENSE_FILE);
+
+        match Activation::from_token(&token) {
+            Some(saved) => match saved.verify().await {
+                Ok((_, token)) => {
+                    if let Err(err) = write(Path::new(LICENSE_FILE), token) {
+                        eprintln!("Can't save the license token: {}", err);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("License check failed: {}", err.as_str());
+                    return 1;
+                }
+            },
+            None => {
+                eprintln!("The application is not activated, activate it in the window mode");
+                return 1;
+            }
+        }
+
+        let mut progress = stream::select_all(
+            tasks
+                .iter()
+                .filter(|task| task.progress.is_running())
+                .map(|task| {
+                    let uid = task.uid;
+                    task.run().map(move |progress| (uid, progress)).boxed()
+                })
+                .collect::<Vec<_>>(),
+        );
+        let mut last = HashMap::new();
+
+        while let Some((uid, state)) = progress.next().await {
+            print(uid, &state, args.json);
+
+            if let Some(task) = tasks.iter_mut().find(|task| task.uid == uid) {
+                task.progress = state.clone();
+
+                // The next run picks up where this one has stopped
+                if !state.is_running() {
+                    save(&args.tasks, &tasks);
+                }
+            }
+            last.insert(uid, state);
+        }
+
+        if last
+            .values()
+            .all(|state| matches!(state, TaskProgress::Complete(_)))
+        {
+            0
+        } else {
+            1
+        }
+    })
+}
+
+fn save(file: &str, tasks: &[Task]) {
+    if let Err(err) = File::create(Path::new(file))
+        .map_err(|err| err.to_string())
+        .and_then(|file| to_writer(file, tasks).map_err(|err| err.to_string()))
+    {
+        eprintln!("Can't save the tasks to {}: {}", file, err);
+    }
+}
+
+fn print(uid: u64, progress: &TaskProgress, json: bool) {
+    if json {
+        println!("{}", progress.event(uid));
+    } else {
+        println!("[#{}] {}", uid, progress.to_str());
+    }
+}
@@ -2,9 +2,12 @@ use std::{
     collections::BTreeMap,
     fs::{remove_file, write, File},
     path::Path,
-    time::Duration,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
+use api::checkout::{Item, PriceGuard};
+use hyper::StatusCode;
 use iced::{
     button, executor, time::every, Application, Clipboard, Command, Element, Subscription, Text,
 };
@@ -14,13 +17,17 @@ use iced_native::{
     window::Event as WinEvent,
 };
 use serde::Deserialize;
-use serde_json::{from_reader, to_writer};
+use serde_json::{from_reader, json, to_writer};
 use tokio::time::sleep;
 
 use crate::{
     logic::{
         activation::{Activation, ActivationError},
-        models::{ProductCard, Settings, Size, Variant},
+        control::{self, Action, Control, Events},
+        embeds::Embeds,
+        logs,
+        models::Settings,
+        notify::Notifiers,
         task::{Task, TaskMsg, TaskProgress},
     },
     themes::Theme,
@@ -34,7 +41,7 @@ use crate::{
         },
         View, ViewMsg, ViewState,
     },
-    ACCOUNTS_FILE, LICENSE_FILE, PROXY_FILE, SETTINGS_FILE,
+    ACCOUNTS_FILE, LICENSE_FILE, PROXY_FILE, SETTINGS_FILE, TASKS_FILE,
 };
 use iced_native::event::Status;
 
@@ -52,12 +59,9 @@ pub enum Message {
     Proxy(usize, ProxyMsg),
     NewProxy,
     Task(u64, TaskMsg),
-    AddTasks {
-        card: ProductCard,
-        variant: Variant,
-        size: Size,
-    },
+    AddTasks(Vec<Item>, PriceGuard, Option<SystemTime>),
     TaskProgressed((u64, TaskProgress)),
+    Control(Control),
 
     Activation {
         activation: Activation,
@@ -96,6 +100,8 @@ pub struct Layout {
 
     task_counter: u64,
     tasks: BTreeMap<u64, Task>,
+    /// Task progress for the control API clients
+    events: Events,
 
     token: String,
     activation: Option<Activation>,
@@ -157,6 +163,14 @@ impl Layout {
             }
         }
 
+        if !self.tasks.is_empty() || Path::new(TASKS_FILE).exists() {
+            to_writer(
+                File::create(Path::new(TASKS_FILE)).unwrap(),
+                &self.tasks.values().collect::<Vec<&Task>>(),
+            )
+            .unwrap();
+        }
+
         if !self.token.is_empty() {
             write(Path::new(LICENSE_FILE), &self.token).unwrap();
         }
@@ -164,6 +178,78 @@ impl Layout {
         self.exit = true
     }
 
+    /// Answer of a control API call
+    fn control(
+        &mut self,
+        action: Action,
+        clipboard: &mut Clipboard,
+    ) -> (StatusCode, serde_json::Value, Command<Message>) {
+        match action {
+            Action::List => (
+                StatusCode::OK,
+                json!(self.tasks.values().map(Task::summary).collect::<Vec<_>>()),
+                Command::none(),
+            ),
+            Action::Create(items, guard, drop) => {
+                let (first, tab) = (self.task_counter + 1, self.tab);
+                let command = self.update(Message::AddTasks(items, guard, drop), clipboard);
+                let created = (first..=self.task_counter).collect::<Vec<u64>>();
+                self.tab = tab;
+
+                if created.is_empty() {
+                    (
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        json!({ "error": "No active accounts" }),
+                        command,
+                    )
+                } else {
+                    (StatusCode::CREATED, json!({ "tasks": created }), command)
+                }
+            }
+            Action::Task(uid, msg) => {
+                let progress = match self.tasks.get(&uid) {
+                    Some(task) => &task.progress,
+                    None => {
+                        return (
+                            StatusCode::NOT_FOUND,
+                            json!({ "error": "Unknown task" }),
+                            Command::none(),
+                        )
+                    }
+                };
+                let conflict = match msg {
+                    TaskMsg::Retry => match progress {
+                        TaskProgress::Error(_) | TaskProgress::Failed(_) | TaskProgress::Idle => {
+                            None
+                        }
+                        _ => Some("Task can't be started"),
+                    },
+                    TaskMsg::Stop if !progress.is_running() => Some("Task is not running"),
+                    _ => None,
+                };
+
+                if let Some(err) = conflict {
+                    return (
+                        StatusCode::CONFLICT,
+                        json!({ "error": err }),
+                        Command::none(),
+                    );
+                }
+
+                let command = self.update(Message::Task(uid, msg), clipboard);
+
+                match self.tasks.get(&uid) {
+                    Some(task) => (StatusCode::OK, task.summary(), command),
+                    None => (
+                        StatusCode::OK,
+                        json!({ "id": uid, "deleted": true }),
+                        command,
+                    ),
+                }
+            }
+        }
+    }
+
     async fn activation_check(token: &String) -> Message {
         match Activation::from_token(token) {
             Some(saved) => {
@@ -190,10 +276,25 @@ impl Application for Layout {
         let mut accounts = Vec::new();
         let mut proxies = Vec::new();
         let mut settings = Settings::default();
+        let mut tasks: Vec<Task> = Vec::new();
 
         load_file(ACCOUNTS_FILE, &mut accounts);
         load_file(PROXY_FILE, &mut proxies);
         load_file(SETTINGS_FILE, &mut settings);
+        load_file(TASKS_FILE, &mut tasks);
+        settings.embeds = Arc::new(Embeds::load());
+
+        let tasks = tasks
+            .into_iter()
+            .map(|mut task| {
+                task.restore(
+                    settings.site(),
+                    Notifiers::new(&settings),
+                    settings.autostart,
+                );
+                (task.uid, task)
+            })
+            .collect::<BTreeMap<u64, Task>>();
 
         let token = Activation::load_token(LICENSE_FILE);
 
@@ -202,6 +303,8 @@ impl Application for Layout {
                 settings,
                 accounts,
                 proxies,
+                task_counter: tasks.keys().last().copied().unwrap_or(0),
+                tasks,
                 token: token.clone(),
                 tab: 1,
                 tabs: vec![
@@ -231,6 +334,11 @@ impl Application for Layout {
                         Tab::Proxy(Default::default()),
                         Default::default(),
                     ),
+                    (
+                        String::from("History"),
+                        Tab::History(Default::default()),
+                        Default::default(),
+                    ),
                 ],
                 ..Default::default()
             },
@@ -248,7 +356,7 @@ impl Application for Layout {
     fn update(
         &mut self,
         message: Self::Message,
-        _clipboard: &mut Clipboard,
+        clipboard: &mut Clipboard,
     ) -> Command<Self::Message> {
         match message {
             Message::View(view) => {
@@ -262,21 +370,32 @@ impl Application for Layout {
                     }
                 }
             },
-            Message::Tab(tab) => self.tab = tab,
+            Message::Tab(tab) => {
+                if let Tab::History(ref mut state) = self.tabs[tab].1 {
+                    state.reload();
+                }
+
+                self.tab = tab
+            }
             Message::TabMsg(tab) => match tab {
                 TabMsg::SettingsMsg(msg) => {
                     if let Tab::Settings(ref mut state) = self.tabs[0].1 {
-                        state.update(msg, &mut self.settings)
+                        return state.update(msg, &mut self.settings);
                     }
                 }
                 TabMsg::AddTasksMsg(msg) => {
                     if let Tab::AddTasks(ref mut state) = self.tabs[3].1 {
-                        return state.update(msg);
+                        return state.update(msg, self.settings.site());
                     }
                 }
                 TabMsg::AccountsMsg(msg) => {
                     if let Tab::Accounts(ref mut state) = self.tabs[4].1 {
-                        return state.update(msg, &mut self.accounts);
+                        return state.update(msg, &mut self.accounts, self.settings.site());
+                    }
+                }
+                TabMsg::HistoryMsg(msg) => {
+                    if let Tab::History(ref mut state) = self.tabs[6].1 {
+                        state.update(msg)
                     }
                 }
             },
@@ -287,13 +406,11 @@ impl Application for Layout {
             Message::NewProxy => self.proxies.push(Proxy::new()),
             Message::Task(id, TaskMsg::Delete) => {
                 self.tasks.remove(&id);
+                logs::forget(id);
             }
             Message::Task(id, msg) => self.tasks.get_mut(&id).unwrap().update(msg),
-            Message::AddTasks {
-                card,
-                variant,
-                size,
-            } => {
+            Message::AddTasks(items, guard, drop) => {
+                let schedule = drop.map(|d| (d, Duration::from_secs(self.settings.warmup_lead)));
                 let mut proxies = self
                     .proxies
                     .iter()
@@ -319,6 +436,7 @@ impl Application for Layout {
                     })
                     .count();
                 let account_count = self.accounts.iter().filter(|a| a.active).count();
+                let site = self.settings.site();
 
                 let iterator = self.accounts.iter().filter(|a| a.active).rev().skip(
                     if let ProxyMode::Strict = self.settings.proxy_mode {
@@ -354,19 +472,22 @@ impl Application for Layout {
                         self.task_counter,
                         Task::new(
                             self.task_counter,
+                            site.clone(),
                             p,
-                            card.clone(),
-                            variant.clone(),
-                            size.clone(),
+                            items.clone(),
                             (a.phone.clone(), a.token.clone()),
-                            self.settings.webhook.clone(),
+                            Notifiers::new(&self.settings),
                             (
                                 self.settings.limiter,
                                 self.settings.force,
                                 self.settings.monitor,
                                 self.settings.monitor_freq,
                             ),
-                        ),
+                            self.settings.retry,
+                            guard,
+                            schedule,
+                        )
+                        .capture(self.settings.capture),
                     ) {
                         Some(_) => panic!(),
                         None => (),
@@ -376,9 +497,17 @@ impl Application for Layout {
                 self.tab = 2;
             }
             Message::TaskProgressed((uid, state)) => match self.tasks.get_mut(&uid) {
-                Some(task) => task.progress = state,
+                Some(task) => {
+                    self.events.publish(uid, &state);
+                    task.progress = state
+                }
                 None => (),
             },
+            Message::Control(control) => {
+                let (status, body, command) = self.control(control.action.clone(), clipboard);
+                control.reply(status, body);
+                return command;
+            }
             Message::Activation { activation, token } => {
                 self.activation = Some(activation);
                 self.token = token;
@@ -448,10 +577,16 @@ impl Application for Layout {
                 0 => self.settings.limiter = set,
                 1 => self.settings.force = set,
                 2 => self.settings.monitor = set,
+                8 => self.settings.autostart = set,
+                9 => self.settings.capture = set,
                 _ => {}
             },
             Message::ExperimentalNumber(flag, num) => match flag {
                 3 => self.settings.monitor_freq = num,
+                4 => self.settings.retry.attempts = num as u32,
+                5 => self.settings.retry.backoff = num,
+                6 => self.settings.retry.jitter = num,
+                7 => self.settings.warmup_lead = num,
                 _ => {}
             },
             Message::ResetAppearance => {
@@ -487,6 +622,19 @@ impl Application for Layout {
 
         subs.extend(self.tasks.values().map(Task::subscription));
 
+        let api = &self.settings.control;
+        if self.activation.is_some() && api.enabled && !api.token.is_empty() {
+            subs.push(
+                Subscription::from_recipe(control::Server {
+                    port: api.port,
+                    token: api.token.clone(),
+                    site: self.settings.site(),
+                    events: self.events.clone(),
+                })
+                .map(Message::Control),
+            );
+        }
+
         Subscription::batch(subs)
     }
 
@@ -524,7 +672,7 @@ impl Application for Layout {
     }
 }
 
-fn load_file<T: for<'de> Deserialize<'de>>(path: &str, object: &mut T) {
+pub fn load_file<T: for<'de> Deserialize<'de>>(path: &str, object: &mut T) {
     let file = Path::new(path);
     if file.exists() {
         match from_reader::<File, T>(File::open(file).unwrap()) {
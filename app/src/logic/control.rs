@@ -0,0 +1,575 @@
+use std::{
+    convert::Infallible,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use api::{
+    checkout::{Item, PriceGuard},
+    client::WildberriesClient,
+    log::{Level, Record, Tag},
+    models::ResponseValue,
+    site::Site,
+};
+use hyper::{
+    header::{
+        HeaderMap, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_TYPE, SEC_WEBSOCKET_ACCEPT,
+        SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+    },
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server as HttpServer, StatusCode,
+};
+use iced_futures::futures::{
+    channel::{mpsc, oneshot},
+    stream, SinkExt, StreamExt,
+};
+use iced_native::subscription::Recipe;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message as WsMessage},
+    WebSocketStream,
+};
+
+use crate::logic::task::{TaskMsg, TaskProgress};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Control API
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// What a control API call asks the application to do
+#[derive(Clone, Debug)]
+pub enum Action {
+    List,
+    Create(Vec<Item>, PriceGuard, Option<SystemTime>),
+    Task(u64, TaskMsg),
+}
+
+/// Control API call waiting for the application to answer
+#[derive(Clone, Debug)]
+pub struct Control {
+    pub action: Action,
+    reply: Reply,
+}
+
+impl Control {
+    /// Answers the call with the status and JSON body, only the first answer is sent
+    pub fn reply(&self, status: StatusCode, body: serde_json::Value) {
+        if let Some(sender) = self.reply.0.lock().unwrap().take() {
+            let _ = sender.send((status, body));
+        }
+    }
+}
+
+/// Status and JSON body of an answer
+type Answer = (StatusCode, serde_json::Value);
+
+#[derive(Clone)]
+struct Reply(Arc<Mutex<Option<oneshot::Sender<Answer>>>>);
+
+impl Debug for Reply {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Reply")
+    }
+}
+
+/// Progress events of the tasks, streamed to the WebSocket clients
+#[derive(Clone)]
+pub struct Events(broadcast::Sender<String>);
+
+impl Events {
+    pub fn publish(&self, uid: u64, progress: &TaskProgress) {
+        // Nobody is listening unless a client is connected
+        let _ = self.0.send(progress.event(uid).to_string());
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events(broadcast::channel(256).0)
+    }
+}
+
+/// HTTP server of the control API on localhost, it runs while the subscription is alive
+pub struct Server {
+    pub port: u16,
+    pub token: String,
+    pub site: Site,
+    pub events: Events,
+}
+
+impl<H, I> Recipe<H, I> for Server
+where
+    H: Hasher,
+{
+    type Output = Control;
+
+    fn hash(&self, state: &mut H) {
+        struct Marker;
+        std::any::TypeId::of::<Marker>().hash(state);
+
+        self.port.hash(state);
+        self.token.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: stream::BoxStream<I>) -> stream::BoxStream<Self::Output> {
+        let Server {
+            port,
+            token,
+            site,
+            events,
+        } = *self;
+        let (sender, receiver) = mpsc::unbounded();
+        let shared = Arc::new(Shared {
+            token,
+            site,
+            calls: sender,
+            events,
+        });
+
+        let server = stream::once(async move {
+            if let Err(err) = serve(port, shared).await {
+                Record::new(
+                    Level::Error,
+                    Tag::default(),
+                    format!("Control API stopped: {}", err),
+                )
+                .log();
+            }
+        })
+        .filter_map(|_| async { None::<Control> });
+
+        Box::pin(stream::select(server, receiver))
+    }
+}
+
+struct Shared {
+    token: String,
+    site: Site,
+    calls: mpsc::UnboundedSender<Control>,
+    events: Events,
+}
+
+async fn serve(port: u16, shared: Arc<Shared>) -> hyper::Result<()> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let service = make_service_fn(move |_| {
+        let shared = shared.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(req, shared.clone()))) }
+    });
+
+    HttpServer::try_bind(&addr)?.serve(service).await
+}
+
+async fn handle(req: Request<Body>, shared: Arc<Shared>) -> Result<Response<Body>, Infallible> {
+    if !authorized(&req, &shared.token) {
+        return Ok(respond(StatusCode::UNAUTHORIZED, "Invalid token"));
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let segments = path.split('/').skip(1).collect::<Vec<&str>>();
+
+    if method == Method::GET && segments == ["events"] {
+        return Ok(upgrade(req, &shared));
+    }
+
+    let action = match (method, segments.as_slice()) {
+        (Method::GET, ["tasks"]) => Ok(Action::List),
+        (Method::POST, ["tasks"]) => create(req, &shared.site)
+            .await
+            .map_err(|err| Refusal(StatusCode::BAD_REQUEST, err)),
+        (Method::POST, ["tasks", id, "start"]) => task(id, TaskMsg::Retry),
+        (Method::POST, ["tasks", id, "stop"]) => task(id, TaskMsg::Stop),
+        (Method::DELETE, ["tasks", id]) => task(id, TaskMsg::Delete),
+        _ => Err(Refusal::new(StatusCode::NOT_FOUND, "Unknown endpoint")),
+    };
+    let action = match action {
+        Ok(action) => action,
+        Err(refusal) => return Ok(refusal.response()),
+    };
+
+    let (sender, receiver) = oneshot::channel();
+    let control = Control {
+        action,
+        reply: Reply(Arc::new(Mutex::new(Some(sender)))),
+    };
+
+    if shared.calls.unbounded_send(control).is_err() {
+        return Ok(respond(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Application is closing",
+        ));
+    }
+
+    Ok(match receiver.await {
+        Ok((status, body)) => json_response(status, body),
+        Err(_) => respond(StatusCode::SERVICE_UNAVAILABLE, "Application is closing"),
+    })
+}
+
+/// Bearer token of the `Authorization` header, or the `token` query parameter for the clients
+/// which can't set headers, like browser WebSockets
+fn authorized(req: &Request<Body>, token: &str) -> bool {
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|val| val.to_str().ok())
+        .and_then(|val| val.strip_prefix("Bearer "));
+    let query = req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+
+    header
+        .or(query)
+        .is_some_and(|given| same(given.as_bytes(), token.as_bytes()))
+}
+
+/// Compares in constant time, so the token can't be guessed from the response times
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn task(id: &str, msg: TaskMsg) -> Result<Action, Refusal> {
+    match id.parse::<u64>() {
+        Ok(id) => Ok(Action::Task(id, msg)),
+        Err(_) => Err(Refusal::new(StatusCode::NOT_FOUND, "Unknown task")),
+    }
+}
+
+/// Call refused before it reaches the application, with the status and the error message
+#[derive(Debug)]
+struct Refusal(StatusCode, String);
+
+impl Refusal {
+    fn new(status: StatusCode, msg: &str) -> Refusal {
+        Refusal(status, msg.to_string())
+    }
+
+    fn response(self) -> Response<Body> {
+        let mut resp = respond(self.0, &self.1);
+
+        // The version the client should switch to
+        if self.0 == StatusCode::UPGRADE_REQUIRED {
+            resp.headers_mut()
+                .insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+        }
+
+        resp
+    }
+}
+
+fn respond(status: StatusCode, msg: &str) -> Response<Body> {
+    json_response(status, json!({ "error": msg }))
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Task creation
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize)]
+struct CreateBody {
+    items: Vec<ItemSpec>,
+    #[serde(default)]
+    guard: PriceGuard,
+    /// Drop time (unix time, s), the tasks start right away if none
+    drop: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ItemSpec {
+    cod: u64,
+    /// Size names in priority order, any size if empty
+    #[serde(default)]
+    sizes: Vec<String>,
+    #[serde(default = "ItemSpec::quantity")]
+    quantity: u64,
+}
+
+impl ItemSpec {
+    fn quantity() -> u64 {
+        1
+    }
+}
+
+async fn create(req: Request<Body>, site: &Site) -> Result<Action, String> {
+    let body = hyper::body::to_bytes(req.into_body())
+        .await
+        .map_err(|err| err.to_string())?;
+    let body = serde_json::from_slice::<CreateBody>(&body).map_err(|err| err.to_string())?;
+
+    if body.items.is_empty() {
+        return Err(String::from("No items"));
+    }
+
+    let mut items = Vec::new();
+    for spec in body.items {
+        items.push(item(site, spec).await?);
+    }
+
+    Ok(Action::Create(
+        items,
+        body.guard,
+        body.drop.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+    ))
+}
+
+/// Item of the product fetched from the site
+async fn item(site: &Site, spec: ItemSpec) -> Result<Item, String> {
+    let value = match WildberriesClient::new(site.clone(), None, None)
+        .product_data(spec.cod, 0)
+        .await
+    {
+        Ok(result) => match result.value {
+            ResponseValue::Value(value) => value,
+            _ => return Err(format!("Product {} scheme error", spec.cod)),
+        },
+        Err(_) => return Err(format!("Product {} not found", spec.cod)),
+    };
+    let (card, variant) = match (value.data.product_card, value.data.variant) {
+        (Some(card), Some(variant)) => (card, variant),
+        _ => return Err(format!("Can't parse product {}", spec.cod)),
+    };
+
+    let sizes = spec
+        .sizes
+        .iter()
+        .map(|name| {
+            variant
+                .sizes
+                .values()
+                .find(|s| &s.name == name)
+                .map(|s| s.id)
+                .ok_or_else(|| format!("Product {} has no size {}", spec.cod, name))
+        })
+        .collect::<Result<Vec<u64>, String>>()?;
+    let size = match sizes.first() {
+        Some(id) => variant.sizes.get(&id.to_string()),
+        None => variant.sizes.values().next(),
+    }
+    .cloned()
+    .ok_or_else(|| format!("Product {} has no sizes", spec.cod))?;
+
+    Ok(Item::new(card, variant, size, spec.quantity.max(1)).sizes(sizes))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Progress WebSocket
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Switches the connection to a WebSocket which gets a text message per progress event. Pings
+/// are answered and the socket is dropped once the client closes it or goes away
+fn upgrade(req: Request<Body>, shared: &Shared) -> Response<Body> {
+    let accept = match handshake(req.headers()) {
+        Ok(accept) => accept,
+        Err(refusal) => return refusal.response(),
+    };
+    let mut events = shared.events.0.subscribe();
+    let calls = shared.calls.clone();
+
+    tokio::spawn(async move {
+        let io = match hyper::upgrade::on(req).await {
+            Ok(io) => io,
+            Err(_) => return,
+        };
+        let mut ws = WebSocketStream::from_raw_socket(io, Role::Server, None).await;
+
+        loop {
+            tokio::select! {
+                event = events.recv() => {
+                    let event = match event {
+                        Ok(event) => event,
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    };
+
+                    // The server is gone once the API is turned off
+                    if calls.is_closed() || ws.send(WsMessage::Text(event)).await.is_err() {
+                        break;
+                    }
+                }
+                // Pongs and the close reply are sent by the next read, which ends the stream
+                // after a close
+                msg = ws.next() => if !matches!(msg, Some(Ok(_))) {
+                    return;
+                },
+            }
+        }
+
+        let _ = ws.close(None).await;
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// `Sec-WebSocket-Accept` of a valid RFC 6455 upgrade request
+fn handshake(headers: &HeaderMap) -> Result<String, Refusal> {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|val| val.to_str().ok())
+            .unwrap_or_default()
+    };
+
+    let connection = header(CONNECTION)
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+    if !connection || !header(UPGRADE).trim().eq_ignore_ascii_case("websocket") {
+        return Err(Refusal::new(
+            StatusCode::BAD_REQUEST,
+            "WebSocket upgrade expected",
+        ));
+    }
+
+    if header(SEC_WEBSOCKET_VERSION).trim() != "13" {
+        return Err(Refusal::new(
+            StatusCode::UPGRADE_REQUIRED,
+            "Unsupported WebSocket version",
+        ));
+    }
+
+    match headers.get(SEC_WEBSOCKET_KEY) {
+        Some(key) if !key.is_empty() => Ok(derive_accept_key(key.as_bytes())),
+        _ => Err(Refusal::new(
+            StatusCode::BAD_REQUEST,
+            "WebSocket key expected",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use api::site::Site;
+    use hyper::{
+        header::{CONNECTION, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE},
+        HeaderMap, StatusCode,
+    };
+    use iced_futures::futures::{channel::mpsc, SinkExt, StreamExt};
+    use tokio_tungstenite::{connect_async, tungstenite::Message as WsMessage};
+
+    use super::{handshake, same, serve, Events, Shared};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    /// Handshake of RFC 6455, section 1.3
+    const RFC_REQUEST: [(&str, &str); 4] = [
+        ("connection", "keep-alive, Upgrade"),
+        ("upgrade", "websocket"),
+        ("sec-websocket-version", "13"),
+        ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="),
+    ];
+
+    #[test]
+    fn accept_key() {
+        assert_eq!(
+            handshake(&headers(&RFC_REQUEST)).unwrap(),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn invalid_upgrades() {
+        let without = |name| {
+            let mut headers = headers(&RFC_REQUEST);
+            headers.remove(name);
+            handshake(&headers).unwrap_err().response()
+        };
+
+        for name in [CONNECTION, UPGRADE, SEC_WEBSOCKET_KEY] {
+            assert_eq!(without(name).status(), StatusCode::BAD_REQUEST);
+        }
+
+        let resp = without(SEC_WEBSOCKET_VERSION);
+        assert_eq!(resp.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(resp.headers()[SEC_WEBSOCKET_VERSION], "13");
+
+        let mut old = headers(&RFC_REQUEST);
+        old.insert(SEC_WEBSOCKET_VERSION, "8".parse().unwrap());
+        assert_eq!(
+            handshake(&old).unwrap_err().response().status(),
+            StatusCode::UPGRADE_REQUIRED
+        );
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(same(b"secret", b"secret"));
+        assert!(!same(b"secret", b"secreT"));
+        assert!(!same(b"secret", b"secret2"));
+        assert!(!same(b"", b"secret"));
+    }
+
+    #[tokio::test]
+    async fn events_socket() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (calls, _receiver) = mpsc::unbounded();
+        let events = Events::default();
+        let shared = Arc::new(Shared {
+            token: String::from("TOKEN"),
+            site: Site::default(),
+            calls,
+            events: events.clone(),
+        });
+        tokio::spawn(serve(port, shared));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let url = format!("ws://127.0.0.1:{}/events", port);
+        assert!(connect_async(&url).await.is_err());
+
+        let (mut ws, _) = connect_async(&format!("{}?token=TOKEN", url))
+            .await
+            .unwrap();
+
+        ws.send(WsMessage::Ping(vec![1, 2])).await.unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            WsMessage::Pong(vec![1, 2])
+        );
+
+        events.0.send(String::from("{}")).unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            WsMessage::Text(String::from("{}"))
+        );
+
+        // The server answers the close and lets the socket go
+        ws.close(None).await.unwrap();
+        assert!(matches!(
+            ws.next().await,
+            Some(Ok(WsMessage::Close(_))) | None
+        ));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(events.0.receiver_count(), 0);
+    }
+}
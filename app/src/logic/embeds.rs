@@ -0,0 +1,380 @@
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use api::log::{Level, Record, Tag};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    logic::notify::{Event, Report, ReportItem},
+    EDITION, EMBEDS_FILE, VERSION,
+};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Embed templates
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Discord embeds of the reports, user-editable in the embeds file.
+///
+/// Texts may contain the placeholders `{task}`, `{account}`, `{site}`, `{content}`, `{code}`,
+/// `{product}`, `{variant}`, `{size}`, `{quantity}`, `{items}`, `{total}`, `{delivery}`,
+/// `{elapsed}`, `{started}`, `{version}` and `{edition}`. Fields rendered empty are left out
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct Embeds {
+    pub username: String,
+    pub footer: String,
+    pub icon_url: String,
+
+    pub success: Embed,
+    /// Successful order which the site hasn't confirmed
+    pub unconfirmed: Embed,
+    pub user_action: Embed,
+    pub failure: Embed,
+    pub error: Embed,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Embed {
+    pub title: String,
+    pub description: String,
+    pub color: u32,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+impl Field {
+    fn new(name: &str, value: &str, inline: bool) -> Field {
+        Field {
+            name: name.to_string(),
+            value: value.to_string(),
+            inline,
+        }
+    }
+}
+
+impl Embeds {
+    /// Templates of the embeds file. A missing file is created with the default ones, so there is
+    /// something to edit
+    pub fn load() -> Embeds {
+        let path = Path::new(EMBEDS_FILE);
+
+        match read_to_string(path) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(embeds) => embeds,
+                Err(err) => {
+                    Record::new(
+                        Level::Warn,
+                        Tag::default(),
+                        format!(
+                            "Can't parse {}, default embeds are used: {}",
+                            EMBEDS_FILE, err
+                        ),
+                    )
+                    .log();
+                    Embeds::default()
+                }
+            },
+            Err(_) => {
+                let embeds = Embeds::default();
+
+                if let Ok(content) = toml::to_string(&embeds) {
+                    if let Err(err) = write(path, content) {
+                        Record::new(
+                            Level::Warn,
+                            Tag::default(),
+                            format!("Can't write {}: {}", EMBEDS_FILE, err),
+                        )
+                        .log();
+                    }
+                }
+
+                embeds
+            }
+        }
+    }
+
+    fn embed(&self, report: &Report) -> &Embed {
+        match report.event {
+            Event::Success if report.confirmed => &self.success,
+            Event::Success => &self.unconfirmed,
+            Event::UserAction => &self.user_action,
+            Event::Failure => &self.failure,
+            Event::Error => &self.error,
+        }
+    }
+
+    /// Webhook body of the report
+    pub fn render(&self, report: &Report) -> serde_json::Value {
+        let vars = placeholders(report);
+        let text = |template: &str| fill(template, &vars);
+        let embed = self.embed(report);
+
+        let fields = embed
+            .fields
+            .iter()
+            .map(|f| (text(&f.name), text(&f.value), f.inline))
+            .filter(|(name, value, _)| !name.trim().is_empty() && !value.trim().is_empty())
+            .map(|(name, value, inline)| json!({ "name": name, "value": value, "inline": inline }))
+            .collect::<Vec<serde_json::Value>>();
+
+        json!({
+            "username": text(&self.username),
+            "embeds": [{
+                "title": text(&embed.title),
+                "description": text(&embed.description),
+                "color": embed.color,
+                "fields": fields,
+                "footer": {
+                    "text": text(&self.footer),
+                    "icon_url": self.icon_url,
+                }
+            }]
+        })
+    }
+}
+
+impl Default for Embeds {
+    fn default() -> Self {
+        let details = |name: &str, value: &str| {
+            vec![
+                Field::new(name, value, false),
+                Field::new("Task ID", "#{task}", true),
+                Field::new("Account", "||{account}||", true),
+                Field::new("Site", "{site}", true),
+                Field::new("Product", "{product}", false),
+                Field::new("Variant (Color)", "{variant}", true),
+                Field::new("Size", "{size}", true),
+                Field::new("Quantity", "{quantity}", true),
+            ]
+        };
+        let order = |name: &str, value: &str, total: &str| {
+            let mut fields = details(name, value);
+            fields.extend(vec![
+                Field::new(total, "{total} RUB", true),
+                Field::new("Estimated delivery", "{delivery}", true),
+                Field::new("Elapsed", "{elapsed} sec", true),
+                Field::new("Start Time", "{started}", true),
+            ]);
+            fields
+        };
+        let failure = || {
+            let mut fields = details("Description", "{content}");
+            fields.extend(vec![
+                Field::new("Error code", "{code}", true),
+                Field::new("Total", "{total} RUB", true),
+                Field::new("Elapsed", "{elapsed} sec", true),
+                Field::new("Start Time", "{started}", true),
+            ]);
+            fields
+        };
+        let success = Embed {
+            title: String::from("Successful Payment"),
+            description: String::from("Click the link below to see details of your order."),
+            color: 51283,
+            fields: order("Order details", "[Click]({content})", "Total"),
+        };
+
+        Embeds {
+            username: String::from("SDP Pre-Alpha"),
+            footer: String::from("SDP Pre-Alpha (v{version}) (e{edition})"),
+            icon_url: String::from(
+                "https://en.gravatar.com/userimage/182691345/3ce2e13566d08dd3ae6513f6b0404900.png",
+            ),
+
+            unconfirmed: Embed {
+                title: String::from("Successful Payment (Unconfirmed)"),
+                color: 2712319,
+                ..success.clone()
+            },
+            success,
+            user_action: Embed {
+                title: String::from("Bank Payment Confirmation (3D-Secure)"),
+                description: String::from(
+                    "User action required. Click the link below to complete your order payment.",
+                ),
+                color: 16771584,
+                fields: order(
+                    "Payment confirmation",
+                    "[Click]({content})",
+                    "Amount of payment",
+                ),
+            },
+            failure: Embed {
+                title: String::from("Payment Failed"),
+                description: String::new(),
+                color: 16717636,
+                fields: failure(),
+            },
+            error: Embed {
+                title: String::from("Task Error"),
+                description: String::new(),
+                color: 16717636,
+                fields: failure(),
+            },
+        }
+    }
+}
+
+fn placeholders(report: &Report) -> Vec<(&'static str, String)> {
+    let join = |f: fn(&ReportItem) -> String| {
+        report
+            .items
+            .iter()
+            .map(f)
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    vec![
+        ("task", report.task.to_string()),
+        ("account", report.account.clone()),
+        ("site", report.site.to_string()),
+        ("content", report.content.clone()),
+        (
+            "code",
+            report
+                .code
+                .map(|code| format!("E{}", code))
+                .unwrap_or_default(),
+        ),
+        ("product", join(|i| i.product.clone())),
+        (
+            "variant",
+            join(|i| i.variant.clone().unwrap_or_else(|| String::from("-"))),
+        ),
+        ("size", join(|i| i.size.clone())),
+        ("quantity", join(|i| i.quantity.to_string())),
+        ("items", report.items_str()),
+        ("total", report.total.to_string()),
+        (
+            "delivery",
+            report.delivery.clone().unwrap_or_else(|| String::from("-")),
+        ),
+        ("elapsed", format!("{:.3}", report.elapsed)),
+        ("started", report.started.clone()),
+        ("version", VERSION.to_string()),
+        ("edition", EDITION.to_string()),
+    ]
+}
+
+/// Replaces the placeholders in a single pass, so the values are never expanded themselves.
+/// Unknown placeholders are kept as is
+fn fill(text: &str, vars: &[(&'static str, String)]) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let var = rest.find('}').and_then(|end| {
+            vars.iter()
+                .find(|(key, _)| *key == &rest[1..end])
+                .map(|(_, value)| (value, end))
+        });
+        match var {
+            Some((value, end)) => {
+                filled.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill, Embeds};
+    use crate::{
+        logic::notify::{Event, Report},
+        VERSION,
+    };
+
+    fn vars() -> Vec<(&'static str, String)> {
+        vec![
+            ("task", String::from("7")),
+            ("account", String::from("79000000000")),
+            ("content", String::from("Limit is {account} per {task}")),
+        ]
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(fill("Task #{task}", &vars()), "Task #7");
+        assert_eq!(fill("{task}{task}", &vars()), "77");
+        assert_eq!(fill("{{task}}", &vars()), "{7}");
+        assert_eq!(fill("{unknown} {task", &vars()), "{unknown} {task");
+        assert_eq!(fill("", &vars()), "");
+    }
+
+    #[test]
+    fn values_are_not_expanded() {
+        assert_eq!(
+            fill("{content} ({task})", &vars()),
+            "Limit is {account} per {task} (7)"
+        );
+    }
+
+    #[test]
+    fn default_template() {
+        let embeds = Embeds::default();
+        let body = embeds.render(&Report::sample(String::from("https://example.com/order")));
+        let embed = &body["embeds"][0];
+
+        assert_eq!(body["username"], "SDP Pre-Alpha");
+        assert_eq!(embed["title"], "Successful Payment");
+        assert_eq!(
+            embed["fields"][0]["value"],
+            "[Click](https://example.com/order)"
+        );
+        assert_eq!(embed["fields"][1]["value"], "#0");
+        assert!(embed["footer"]["text"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("v{}", VERSION)));
+
+        // The templates survive the embeds file
+        let parsed: Embeds = toml::from_str(&toml::to_string(&embeds).unwrap()).unwrap();
+        assert_eq!(parsed, embeds);
+    }
+
+    #[test]
+    fn empty_fields_are_left_out() {
+        let mut report = Report::sample(String::from("Sold out"));
+        report.event = Event::Error;
+
+        let body = Embeds::default().render(&report);
+        let names = body["embeds"][0]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["name"].as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert!(!names.contains(&String::from("Error code")));
+
+        report.code = Some(9);
+        let body = Embeds::default().render(&report);
+        assert!(body["embeds"][0]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|f| f["name"] == "Error code" && f["value"] == "E9"));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{read_to_string, write, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use api::checkout::EndKind;
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::HISTORY_FILE;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Journal
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Outcome of a checkout which reached the end stage
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Entry {
+    pub task: u64,
+    pub outcome: Outcome,
+    /// Order url, payment confirmation url or bank error
+    pub detail: String,
+    pub product: String,
+    pub size: String,
+    /// Cart total (RUB)
+    pub price: u64,
+    pub account: String,
+    /// Checkout duration (ms)
+    pub elapsed: u64,
+    /// Task start (unix time, s)
+    pub started: u64,
+    /// Outcome time (unix time, s)
+    pub finished: u64,
+}
+
+impl Entry {
+    const CSV_HEADER: &'static str =
+        "task,outcome,detail,product,size,price,account,elapsed_ms,started,finished";
+
+    fn to_csv(&self) -> String {
+        [
+            self.task.to_string(),
+            self.outcome.to_string(),
+            self.detail.clone(),
+            self.product.clone(),
+            self.size.clone(),
+            self.price.to_string(),
+            self.account.clone(),
+            self.elapsed.to_string(),
+            time(self.started),
+            time(self.finished),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<String>>()
+        .join(",")
+    }
+}
+
+#[derive(Deserialize, Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Outcome {
+    Confirmed,
+    Unconfirmed,
+    UserAction,
+    Failed,
+}
+
+impl From<&EndKind> for Outcome {
+    fn from(kind: &EndKind) -> Self {
+        match kind {
+            EndKind::Succeed(true) => Outcome::Confirmed,
+            EndKind::Succeed(false) => Outcome::Unconfirmed,
+            EndKind::UserAction => Outcome::UserAction,
+            EndKind::Failed => Outcome::Failed,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Outcome::Confirmed => "Success",
+                Outcome::Unconfirmed => "Success (Unconfirmed)",
+                Outcome::UserAction => "User Action Required",
+                Outcome::Failed => "Failed",
+            }
+        )
+    }
+}
+
+/// Appends the entry to the journal file
+pub fn append(entry: &Entry) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(HISTORY_FILE))?;
+
+    writeln!(file, "{}", serde_json::to_string(entry)?)
+}
+
+/// Entries of the journal file, oldest first. Damaged lines are skipped
+pub fn load() -> Vec<Entry> {
+    match read_to_string(Path::new(HISTORY_FILE)) {
+        Ok(content) => content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn export_csv(path: &Path, entries: &[&Entry]) -> io::Result<()> {
+    let mut content = String::from(Entry::CSV_HEADER);
+
+    for entry in entries {
+        content.push('\n');
+        content.push_str(&entry.to_csv());
+    }
+
+    write(path, content)
+}
+
+/// Local time of the unix timestamp
+pub fn time(secs: u64) -> String {
+    Local
+        .timestamp(secs as i64, 0)
+        .format("%H:%M:%S %d/%m/%Y")
+        .to_string()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, time, Entry, Outcome};
+
+    /// Fields of a CSV record, with the quoting undone
+    fn parse(record: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = record.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    fields.last_mut().unwrap().push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(String::new()),
+                c => fields.last_mut().unwrap().push(c),
+            }
+        }
+
+        fields
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("5\" screen"), "\"5\"\" screen\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn round_trip() {
+        let entry = Entry {
+            task: 7,
+            outcome: Outcome::Unconfirmed,
+            detail: String::from("Card declined, \"insufficient funds\"\nretry later"),
+            product: String::from("Кроссовки, белые"),
+            size: String::from("M"),
+            price: 3990,
+            account: String::from("Иван"),
+            elapsed: 1520,
+            started: 1_700_000_000,
+            finished: 1_700_000_002,
+        };
+        let record = entry.to_csv();
+
+        assert_eq!(
+            parse(&record),
+            vec![
+                String::from("7"),
+                String::from("Success (Unconfirmed)"),
+                entry.detail.clone(),
+                entry.product.clone(),
+                String::from("M"),
+                String::from("3990"),
+                String::from("Иван"),
+                String::from("1520"),
+                time(entry.started),
+                time(entry.finished),
+            ]
+        );
+        assert_eq!(parse(Entry::CSV_HEADER).len(), parse(&record).len());
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{remove_file, rename, File, OpenOptions},
+    io::{self, Write},
+    sync::{Mutex, OnceLock},
+};
+
+use api::log::{self, Level, Record};
+
+use crate::LOG_FILE;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Logs
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// The log file is rotated once it grows past this size (bytes)
+const MAX_SIZE: u64 = 5 * 1024 * 1024;
+/// Number of rotated files kept next to the log file (`.1` is the newest)
+const ROTATED: usize = 3;
+/// Records of each task kept for the log viewer
+const TASK_RECORDS: usize = 500;
+
+#[cfg(debug_assertions)]
+const MIN_LEVEL: Level = Level::Debug;
+#[cfg(not(debug_assertions))]
+const MIN_LEVEL: Level = Level::Info;
+
+static FILE: Mutex<Option<File>> = Mutex::new(None);
+static TASKS: OnceLock<Mutex<HashMap<u64, VecDeque<Record>>>> = OnceLock::new();
+
+/// Sends the records of the application and the api to the log file (JSON lines) and to the
+/// per-task buffers. Debug builds also print them to stderr
+pub fn init() {
+    log::set_sink(|record| {
+        if record.level < MIN_LEVEL {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        eprintln!("{}", record);
+
+        if let Err(err) = write(&record) {
+            eprintln!("Can't write {}: {}", LOG_FILE, err);
+        }
+
+        if let Some(uid) = record.task {
+            let mut tasks = tasks().lock().unwrap();
+            let records = tasks.entry(uid).or_default();
+
+            if records.len() == TASK_RECORDS {
+                records.pop_front();
+            }
+            records.push_back(record);
+        }
+    });
+}
+
+/// Last `n` records of the task, oldest first
+pub fn task(uid: u64, n: usize) -> Vec<Record> {
+    match tasks().lock().unwrap().get(&uid) {
+        Some(records) => records
+            .iter()
+            .skip(records.len().saturating_sub(n))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Drops the records of a deleted task
+pub fn forget(uid: u64) {
+    tasks().lock().unwrap().remove(&uid);
+}
+
+fn tasks() -> &'static Mutex<HashMap<u64, VecDeque<Record>>> {
+    TASKS.get_or_init(Default::default)
+}
+
+fn write(record: &Record) -> io::Result<()> {
+    let mut file = FILE.lock().unwrap();
+
+    if let Some(ref f) = *file {
+        if f.metadata()?.len() >= MAX_SIZE {
+            *file = None;
+            rotate()?;
+        }
+    }
+
+    if file.is_none() {
+        *file = Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(LOG_FILE)?,
+        );
+    }
+
+    match *file {
+        Some(ref mut f) => writeln!(f, "{}", serde_json::to_string(record)?),
+        None => Ok(()),
+    }
+}
+
+/// Shifts the rotated files by one, the oldest is removed
+fn rotate() -> io::Result<()> {
+    let rotated = |n: usize| format!("{}.{}", LOG_FILE, n);
+
+    match remove_file(rotated(ROTATED)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    for n in (1..ROTATED).rev() {
+        match rename(rotated(n), rotated(n + 1)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    rename(LOG_FILE, rotated(1))
+}
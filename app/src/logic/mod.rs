@@ -1,4 +1,8 @@
 pub mod activation;
-pub mod misc;
+pub mod control;
+pub mod embeds;
+pub mod history;
+pub mod logs;
 pub mod models;
+pub mod notify;
 pub mod task;
@@ -1,11 +1,20 @@
 use std::{
-    collections::BTreeMap,
-    fmt::{Display, Formatter},
+    fmt::{self, Display, Formatter},
+    sync::Arc,
 };
 
+use api::{
+    checkout::RetryPolicy,
+    site::{Site, DEFAULT_SITE_URL},
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
-use crate::{themes::Theme, views::tabs::proxy::ProxyMode};
+use crate::{
+    logic::{embeds::Embeds, notify::Event},
+    themes::Theme,
+    views::tabs::proxy::ProxyMode,
+};
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // System models
@@ -16,7 +25,12 @@ use crate::{themes::Theme, views::tabs::proxy::ProxyMode};
 #[serde(default)]
 pub struct Settings {
     pub webhook: Webhook,
+    pub notifications: Notifications,
+    /// Discord embed templates, kept in their own file
+    #[serde(skip)]
+    pub embeds: Arc<Embeds>,
     pub proxy_mode: ProxyMode,
+    pub site: String,
 
     // Appearance
     #[serde(skip)]
@@ -28,13 +42,24 @@ pub struct Settings {
     pub force: bool,
     pub monitor: bool,
     pub monitor_freq: u64,
+    pub retry: RetryPolicy,
+    /// How long (s) before a scheduled drop the warmup starts
+    pub warmup_lead: u64,
+    /// Run unfinished tasks restored on startup, otherwise they are left idle
+    pub autostart: bool,
+    /// Keep the requests of failed tiers for bug reports
+    pub capture: bool,
+    pub control: ControlApi,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             webhook: Webhook::default(),
+            notifications: Notifications::default(),
+            embeds: Arc::default(),
             proxy_mode: ProxyMode::default(),
+            site: String::from(DEFAULT_SITE_URL),
 
             theme: Theme::Light,
             scale: 1.0,
@@ -43,12 +68,27 @@ impl Default for Settings {
             force: false,
             monitor: true,
             monitor_freq: 1500,
+            retry: RetryPolicy::default(),
+            warmup_lead: 60,
+            autostart: false,
+            capture: false,
+            control: ControlApi::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Site to run against. `SDP_SITE_URL` environment variable takes precedence over settings
+    pub fn site(&self) -> Site {
+        match Site::from_env() {
+            Some(site) => site,
+            None => Site::new(&self.site).unwrap_or_default(),
         }
     }
 }
 
 // Webhook model
-#[derive(Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Webhook {
     pub id: u64,
     pub token: String,
@@ -63,207 +103,271 @@ impl Default for Webhook {
     }
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Site specific models
-////////////////////////////////////////////////////////////////////////////////////////////////////
+impl Webhook {
+    /// Webhook of a URL copied from Discord, like `https://discord.com/api/webhooks/{id}/{token}`
+    pub fn from_url(url: &str) -> Option<Webhook> {
+        let url = url.trim().trim_end_matches('/');
+        let path = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))?;
+        let (host, path) = path.split_at(path.find('/')?);
+
+        if !matches!(
+            host,
+            "discord.com"
+                | "ptb.discord.com"
+                | "canary.discord.com"
+                | "discordapp.com"
+                | "ptb.discordapp.com"
+                | "canary.discordapp.com"
+        ) {
+            return None;
+        }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct ResponseResult {
-    #[serde(rename = "resultState", alias = "ResultState")]
-    pub state: i64,
-    #[serde(alias = "Value", default)]
-    pub value: ResponseValue,
+        let mut parts = path
+            .split('?')
+            .next()?
+            .split('/')
+            .skip_while(|p| *p != "webhooks");
+        parts.next()?;
+        let id = parts.next()?.parse::<u64>().ok()?;
+        let token = parts.next().filter(|t| !t.is_empty())?;
+
+        Some(Webhook {
+            id,
+            token: token.to_string(),
+        })
+    }
 }
 
-#[derive(Deserialize, Clone, Debug)]
-#[serde(untagged)]
-pub enum ResponseValue {
-    Message(String),
-    Value(Value),
-    Order { url: String },
-    Basket(Data),
-    None,
+// Control API model
+/// Localhost API to drive the tasks from scripts
+#[derive(Deserialize, Serialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct ControlApi {
+    pub enabled: bool,
+    pub port: u16,
+    /// Bearer token the clients authorize with
+    pub token: String,
 }
 
-impl Default for ResponseValue {
-    fn default() -> Self {
-        ResponseValue::None
+impl ControlApi {
+    pub fn generate_token() -> String {
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
     }
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct Value {
-    #[serde(alias = "Data")]
-    pub data: Data,
-    #[serde(rename = "userInfo")]
-    pub user: Option<User>,
-}
-
-#[derive(Deserialize, Clone, Debug)]
-pub struct User {
-    pub country: String,
-    #[serde(rename = "lastName")]
-    pub last_name: String,
-    #[serde(rename = "firstName")]
-    pub first_name: String,
-    #[serde(rename = "middleName")]
-    pub middle_name: String,
-    pub phone: u64,
-    #[serde(rename = "formattedPhoneMobile")]
-    pub phone_str: String,
-    #[serde(rename = "someId")]
-    pub id: String,
+impl Default for ControlApi {
+    fn default() -> Self {
+        ControlApi {
+            enabled: false,
+            port: 7878,
+            token: String::new(),
+        }
+    }
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct Data {
-    pub basket: Option<Basket>,
-    #[serde(rename = "basketInfo", alias = "basketShortInfo")]
-    pub basket_info: Option<BasketInfo>,
-    #[serde(rename = "productCard")]
-    pub product_card: Option<ProductCard>,
-    #[serde(rename = "selectedNomenclature")]
-    pub variant: Option<Variant>,
+// Notifications model
+/// Events a notifier is told about
+#[derive(Deserialize, Serialize, PartialEq, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct Route {
+    pub success: bool,
+    pub user_action: bool,
+    pub failure: bool,
+    pub error: bool,
 }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct Basket {
-    #[serde(rename = "paymentType")]
-    pub payment_type: PaymentType,
-    #[serde(rename = "deliveryWays")]
-    pub delivery_ways: Vec<DeliveryWay>,
-    #[serde(rename = "deliveryWay")]
-    pub delivery_way: String,
-    #[serde(rename = "deliveryIntervalTxt")]
-    pub delivery_interval_str: Option<String>,
-    #[serde(rename = "deliveryPoint")]
-    pub delivery_point: DeliveryPoint,
-    #[serde(rename = "includeInOrder")]
-    pub order_items: Vec<u64>,
-    #[serde(rename = "totalPriceToPay")]
-    pub total_price: u64,
-}
+impl Route {
+    pub fn allows(&self, event: Event) -> bool {
+        match event {
+            Event::Success => self.success,
+            Event::UserAction => self.user_action,
+            Event::Failure => self.failure,
+            Event::Error => self.error,
+        }
+    }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct PaymentType {
-    pub id: String,
-    #[serde(rename = "bankCardId")]
-    pub card: String,
+    pub fn set(&mut self, event: Event, value: bool) {
+        match event {
+            Event::Success => self.success = value,
+            Event::UserAction => self.user_action = value,
+            Event::Failure => self.failure = value,
+            Event::Error => self.error = value,
+        }
+    }
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct BasketInfo {
-    #[serde(rename = "isAuthenticated")]
-    pub is_auth: bool,
-    #[serde(rename = "basketQuantity")]
-    pub quantity: u64,
-    #[serde(rename = "eventsCount")]
-    pub events_count: u64,
+impl Default for Route {
+    fn default() -> Self {
+        Route {
+            success: true,
+            user_action: true,
+            failure: true,
+            error: false,
+        }
+    }
 }
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct ProductCard {
-    #[serde(rename = "goodsName")]
-    pub name: String,
+#[derive(Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Notifications {
+    /// Routing of the Discord webhook, configured by `Settings::webhook`
+    pub discord: Route,
+    pub telegram: TelegramSink,
+    pub json: UrlSink,
+    pub slack: UrlSink,
+    pub desktop: DesktopSink,
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Delivery
-////////////////////////////////////////////////////////////////////////////////////////////////////
+impl Notifications {
+    pub fn route_mut(&mut self, sink: Sink) -> &mut Route {
+        match sink {
+            Sink::Discord => &mut self.discord,
+            Sink::Telegram => &mut self.telegram.route,
+            Sink::Json => &mut self.json.route,
+            Sink::Slack => &mut self.slack.route,
+            Sink::Desktop => &mut self.desktop.route,
+        }
+    }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct DeliveryWay {
-    pub code: String,
-    pub calendars: Vec<Calendar>,
+    pub fn route(&self, sink: Sink) -> Route {
+        match sink {
+            Sink::Discord => self.discord,
+            Sink::Telegram => self.telegram.route,
+            Sink::Json => self.json.route,
+            Sink::Slack => self.slack.route,
+            Sink::Desktop => self.desktop.route,
+        }
+    }
 }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct Calendar {
-    #[serde(rename = "storeIds")]
-    pub store_ids: Vec<u64>,
-    #[serde(rename = "shippingInterval")]
-    pub shipping_interval: ShippingInterval,
+#[derive(Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TelegramSink {
+    /// Bot token
+    pub token: String,
+    pub chat: String,
+    pub route: Route,
 }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct ShippingInterval {
-    #[serde(rename = "intervalId")]
-    pub id: u64,
-    #[serde(rename = "deliveryDateShort")]
-    pub delivery_date: String,
+#[derive(Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct UrlSink {
+    pub url: String,
+    pub route: Route,
 }
 
-#[derive(Deserialize, Default, Clone, Debug)]
-pub struct DeliveryPoint {
-    #[serde(rename = "kladrId")]
-    pub id: u64,
-    pub address: String,
+#[derive(Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct DesktopSink {
+    pub enabled: bool,
+    pub route: Route,
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Variant
-////////////////////////////////////////////////////////////////////////////////////////////////////
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum Sink {
+    Discord,
+    Telegram,
+    Json,
+    Slack,
+    Desktop,
+}
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct Variant {
-    #[serde(rename = "isSoldOut")]
-    pub sold_out: bool,
-    #[serde(rename = "cod1S")]
-    pub id: u64,
-    #[serde(rename = "rusName")]
-    pub name: Option<String>,
-    pub sizes: BTreeMap<String, Size>,
+impl Sink {
+    pub const ALL: [Sink; 5] = [
+        Sink::Discord,
+        Sink::Telegram,
+        Sink::Json,
+        Sink::Slack,
+        Sink::Desktop,
+    ];
 }
 
-impl Variant {
-    pub fn sizes_tags(&self) -> Vec<SizeTag> {
-        self.sizes.iter().map(|(_, s)| s.as_tag()).collect()
+impl Display for Sink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Sink::Discord => "Discord",
+                Sink::Telegram => "Telegram",
+                Sink::Json => "JSON webhook",
+                Sink::Slack => "Slack",
+                Sink::Desktop => "Desktop",
+            }
+        )
     }
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Size
-////////////////////////////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use super::Webhook;
 
-#[derive(Deserialize, Clone, Debug)]
-pub struct Size {
-    #[serde(rename = "characteristicId")]
-    pub id: u64,
-    #[serde(rename = "sizeName")]
-    pub name: String,
-    #[serde(rename = "price")]
-    pub price: u64,
-    #[serde(rename = "priceWithSale")]
-    pub sale_price: u64,
-    #[serde(rename = "quantity")]
-    pub quantity: u64,
-    #[serde(rename = "isSoldOut")]
-    pub sold_out: bool,
-}
+    fn webhook(id: u64, token: &str) -> Option<Webhook> {
+        Some(Webhook {
+            id,
+            token: token.to_string(),
+        })
+    }
 
-impl Size {
-    pub fn as_tag(&self) -> SizeTag {
-        SizeTag {
-            id: self.id,
-            name: self.name.clone(),
-            quantity: self.quantity,
+    #[test]
+    fn webhook_hosts() {
+        for host in [
+            "discord.com",
+            "discordapp.com",
+            "canary.discord.com",
+            "ptb.discord.com",
+            "canary.discordapp.com",
+            "ptb.discordapp.com",
+        ] {
+            assert_eq!(
+                Webhook::from_url(&format!("https://{}/api/webhooks/123/abc-DEF_1", host)),
+                webhook(123, "abc-DEF_1"),
+                "{}",
+                host
+            );
         }
+
+        assert_eq!(
+            Webhook::from_url("https://example.com/api/webhooks/123/abc"),
+            None
+        );
+        assert_eq!(
+            Webhook::from_url("https://discord.com.example.com/api/webhooks/123/abc"),
+            None
+        );
+        assert_eq!(Webhook::from_url("discord.com/api/webhooks/123/abc"), None);
     }
-}
 
-#[derive(Eq, PartialEq, Clone, Debug)]
-pub struct SizeTag {
-    pub id: u64,
-    pub name: String,
-    pub quantity: u64,
-}
+    #[test]
+    fn webhook_suffixes() {
+        for url in [
+            "https://discord.com/api/webhooks/123/abc/",
+            "https://discord.com/api/webhooks/123/abc?wait=true",
+            "https://discord.com/api/webhooks/123/abc/?wait=true",
+            "  https://discord.com/api/v10/webhooks/123/abc\n",
+        ] {
+            assert_eq!(Webhook::from_url(url), webhook(123, "abc"), "{}", url);
+        }
+    }
 
-impl Display for SizeTag {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.quantity == 0 {
-            write!(f, r#"Size "{}" (Sold Out)"#, self.name)
-        } else {
-            write!(f, r#"Size "{}" (Q: {})"#, self.name, self.quantity)
+    #[test]
+    fn webhook_parts_missing() {
+        for url in [
+            "https://discord.com/api/webhooks/123",
+            "https://discord.com/api/webhooks/123/",
+            "https://discord.com/api/webhooks/123?wait=true",
+            "https://discord.com/api/webhooks//abc",
+            "https://discord.com/api/webhooks/id/abc",
+            "https://discord.com/api/123/abc",
+            "https://discord.com",
+        ] {
+            assert_eq!(Webhook::from_url(url), None, "{}", url);
         }
     }
 }
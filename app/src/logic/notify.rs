@@ -0,0 +1,531 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    process::Command,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use api::{
+    checkout::{Ctx, EndKind},
+    misc::client,
+};
+use async_trait::async_trait;
+use chrono::{Local, TimeZone};
+use iced_futures::futures::future::join_all;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use tokio::{task::spawn_blocking, time::sleep};
+
+use crate::{
+    logic::{
+        embeds::Embeds,
+        models::{Route, Settings, Webhook},
+    },
+    SITE,
+};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Report
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// What a report is sent about, notifications are routed by it
+#[derive(Serialize, Eq, PartialEq, Copy, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Success,
+    /// 3-D Secure payment confirmation is required
+    UserAction,
+    /// Payment failed on the bank side
+    Failure,
+    /// Task stopped with an error
+    Error,
+}
+
+impl Event {
+    pub const ALL: [Event; 4] = [
+        Event::Success,
+        Event::UserAction,
+        Event::Failure,
+        Event::Error,
+    ];
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Event::Success => "Success",
+                Event::UserAction => "3-D Secure",
+                Event::Failure => "Failure",
+                Event::Error => "Error",
+            }
+        )
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ReportItem {
+    pub product: String,
+    pub variant: Option<String>,
+    pub size: String,
+    pub quantity: u64,
+}
+
+/// Everything the notifiers may tell about a task outcome
+#[derive(Serialize, Clone, Debug)]
+pub struct Report {
+    pub event: Event,
+    /// Set for successful orders confirmed by the site
+    pub confirmed: bool,
+    pub task: u64,
+    pub account: String,
+    pub site: &'static str,
+    /// Order url, payment confirmation url, bank error or task error
+    pub content: String,
+    /// Code of the task error
+    pub code: Option<u8>,
+    pub items: Vec<ReportItem>,
+    /// Cart total (RUB)
+    pub total: u64,
+    pub delivery: Option<String>,
+    /// Checkout duration (s)
+    pub elapsed: f32,
+    /// Task start, local time
+    pub started: String,
+}
+
+impl Report {
+    pub fn new(event: Event, content: String, task: u64, account: &str, ctx: &Ctx) -> Report {
+        Report {
+            event,
+            confirmed: false,
+            task,
+            account: account.to_string(),
+            site: SITE,
+            content,
+            code: None,
+            items: ctx
+                .items
+                .iter()
+                .map(|i| ReportItem {
+                    product: i.card.name.clone(),
+                    variant: i.variant.name.clone(),
+                    size: i.size_name().to_string(),
+                    quantity: i.quantity,
+                })
+                .collect(),
+            total: ctx.cart.total_price,
+            delivery: ctx.cart.delivery_interval_str.clone(),
+            elapsed: ctx
+                .start
+                .elapsed()
+                .map(|dur| dur.as_secs_f32())
+                .unwrap_or_default(),
+            started: match ctx.start.duration_since(UNIX_EPOCH) {
+                Ok(dur) => Local
+                    .timestamp(dur.as_secs() as i64, dur.subsec_nanos())
+                    .format("%H:%M:%S %d/%m/%Y")
+                    .to_string(),
+                Err(_) => String::from("-"),
+            },
+        }
+    }
+
+    /// Made up successful order to test the notifiers with
+    pub fn sample(content: String) -> Report {
+        Report {
+            event: Event::Success,
+            confirmed: true,
+            task: 0,
+            account: String::from("70000000000"),
+            site: SITE,
+            content,
+            code: None,
+            items: vec![ReportItem {
+                product: String::from("Test product"),
+                variant: Some(String::from("Test variant")),
+                size: String::from("M"),
+                quantity: 1,
+            }],
+            total: 1000,
+            delivery: Some(String::from("-")),
+            elapsed: 0.0,
+            started: Local::now().format("%H:%M:%S %d/%m/%Y").to_string(),
+        }
+    }
+
+    /// Report of the end stage result, none if the checkout hasn't reached it
+    pub fn end(task: u64, account: &str, ctx: &Ctx) -> Option<Report> {
+        let (content, kind) = ctx.end.as_ref()?;
+
+        Some(match kind {
+            EndKind::Succeed(confirmed) => Report {
+                confirmed: *confirmed,
+                ..Report::new(
+                    Event::Success,
+                    ctx.client.site().url(content),
+                    task,
+                    account,
+                    ctx,
+                )
+            },
+            EndKind::UserAction => {
+                Report::new(Event::UserAction, content.clone(), task, account, ctx)
+            }
+            EndKind::Failed => Report::new(Event::Failure, content.clone(), task, account, ctx),
+        })
+    }
+
+    pub fn code(mut self, code: u8) -> Report {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.event {
+            Event::Success if self.confirmed => "Successful Payment",
+            Event::Success => "Successful Payment (Unconfirmed)",
+            Event::UserAction => "Bank Payment Confirmation (3D-Secure)",
+            Event::Failure => "Payment Failed",
+            Event::Error => "Task Error",
+        }
+    }
+
+    pub fn items_str(&self) -> String {
+        self.items
+            .iter()
+            .map(|i| {
+                format!(
+                    "{} ({}, {}) ×{}",
+                    i.product,
+                    i.variant.as_deref().unwrap_or("-"),
+                    i.size,
+                    i.quantity
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Plain text of the report
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "{}\nTask #{} ({})\n{}\nTotal: {} RUB\n{}",
+            self.title(),
+            self.task,
+            self.site,
+            self.items_str(),
+            self.total,
+            self.content
+        );
+
+        if let Some(code) = self.code {
+            text.push_str(&format!(" [E{}]", code));
+        }
+
+        text
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Notifiers
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A place the reports are sent to
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, report: &Report) -> Result<(), String>;
+}
+
+/// Delivery attempts of a notification
+const ATTEMPTS: u32 = 3;
+/// Longest rate limit wait (s) before the delivery is given up
+const MAX_RETRY_AFTER: f64 = 60.0;
+
+/// Posts JSON to `url`, any status but 2xx is an error. Connection errors and 5xx statuses are
+/// retried with a backoff, 429 ones after the time asked by the server
+async fn post(url: &str, body: &serde_json::Value) -> Result<(), String> {
+    let client = client(None, None, false);
+    let mut attempt = 1;
+
+    loop {
+        let (err, wait) = match client.post(url).json(body).send().await {
+            Ok(resp) if resp.status().is_success() => return Ok(()),
+            Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                let header = resp
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|val| val.to_str().ok())
+                    .and_then(|val| val.parse::<f64>().ok());
+                let wait = match header {
+                    Some(secs) => secs,
+                    None => retry_after(&resp.json().await.unwrap_or_default()),
+                };
+
+                (String::from("rate limited"), wait)
+            }
+            Ok(resp) if resp.status().is_server_error() => {
+                (format!("status {}", resp.status().as_u16()), attempt as f64)
+            }
+            Ok(resp) => return Err(format!("status {}", resp.status().as_u16())),
+            Err(err) => (failure(err), attempt as f64),
+        };
+
+        if attempt >= ATTEMPTS || wait > MAX_RETRY_AFTER {
+            return Err(err);
+        }
+
+        sleep(Duration::from_secs_f64(wait.max(0.0))).await;
+        attempt += 1;
+    }
+}
+
+/// Error of a request without its url, which holds the webhook and bot tokens
+fn failure(err: reqwest::Error) -> String {
+    err.without_url().to_string()
+}
+
+/// Rate limit wait (s) of a 429 response body, Discord puts it at the top level and Telegram
+/// into the parameters
+fn retry_after(body: &serde_json::Value) -> f64 {
+    body.get("retry_after")
+        .or_else(|| body.pointer("/parameters/retry_after"))
+        .and_then(|val| val.as_f64())
+        .unwrap_or(1.0)
+}
+
+/// Discord webhook embed of the templates
+pub struct Discord(pub Webhook, pub Arc<Embeds>);
+
+impl Discord {
+    fn url(&self) -> String {
+        format!(
+            "https://discord.com/api/webhooks/{}/{}",
+            self.0.id, self.0.token
+        )
+    }
+
+    /// Sends the sample report once, the HTTP status is an error unless it's 2xx
+    pub async fn test(&self, report: &Report) -> Result<String, String> {
+        match client(None, None, false)
+            .post(self.url())
+            .json(&self.1.render(report))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => Ok(resp.status().to_string()),
+            Ok(resp) => Err(resp.status().to_string()),
+            Err(err) => Err(failure(err)),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "Discord"
+    }
+
+    async fn send(&self, report: &Report) -> Result<(), String> {
+        post(&self.url(), &self.1.render(report)).await
+    }
+}
+
+/// Telegram bot message, (bot token, chat id)
+pub struct Telegram(pub String, pub String);
+
+#[async_trait]
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "Telegram"
+    }
+
+    async fn send(&self, report: &Report) -> Result<(), String> {
+        post(
+            &format!("https://api.telegram.org/bot{}/sendMessage", self.0),
+            &json!({
+                "chat_id": self.1,
+                "text": report.text(),
+                "disable_web_page_preview": true,
+            }),
+        )
+        .await
+    }
+}
+
+/// The report as is, posted to any url
+pub struct JsonHook(pub String);
+
+#[async_trait]
+impl Notifier for JsonHook {
+    fn name(&self) -> &'static str {
+        "JSON"
+    }
+
+    async fn send(&self, report: &Report) -> Result<(), String> {
+        post(&self.0, &json!(report)).await
+    }
+}
+
+/// Slack-compatible incoming webhook
+pub struct Slack(pub String);
+
+#[async_trait]
+impl Notifier for Slack {
+    fn name(&self) -> &'static str {
+        "Slack"
+    }
+
+    async fn send(&self, report: &Report) -> Result<(), String> {
+        post(&self.0, &json!({ "text": report.text() })).await
+    }
+}
+
+/// Notification of the desktop environment
+pub struct Desktop;
+
+impl Desktop {
+    #[cfg(target_os = "linux")]
+    fn command(title: &str, body: &str) -> Command {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=SDP", title, body]);
+        command
+    }
+
+    #[cfg(target_os = "windows")]
+    fn command(title: &str, body: &str) -> Command {
+        let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 $n = New-Object System.Windows.Forms.NotifyIcon; \
+                 $n.Icon = [System.Drawing.SystemIcons]::Information; \
+                 $n.Visible = $true; \
+                 $n.ShowBalloonTip(10000, {}, {}, 'Info'); \
+                 Start-Sleep -Seconds 10; $n.Dispose()",
+                quote(title),
+                quote(body)
+            ),
+        ]);
+        command
+    }
+}
+
+#[async_trait]
+impl Notifier for Desktop {
+    fn name(&self) -> &'static str {
+        "Desktop"
+    }
+
+    async fn send(&self, report: &Report) -> Result<(), String> {
+        let body = format!("Task #{}: {}", report.task, report.items_str());
+
+        match Desktop::command(report.title(), &body).spawn() {
+            Ok(mut child) => {
+                // Waited for aside, so it doesn't stay a zombie. The Windows balloon runs for seconds
+                spawn_blocking(move || child.wait());
+                Ok(())
+            }
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+/// Notifiers of a task with the events routed to them
+#[derive(Clone, Default)]
+pub struct Notifiers(Vec<(Arc<dyn Notifier>, Route)>);
+
+impl Notifiers {
+    /// Every notifier configured in the settings
+    pub fn new(settings: &Settings) -> Notifiers {
+        let config = &settings.notifications;
+        let mut notifiers: Vec<(Arc<dyn Notifier>, Route)> = Vec::new();
+
+        if settings.webhook.id != 0 && !settings.webhook.token.is_empty() {
+            notifiers.push((
+                Arc::new(Discord(settings.webhook.clone(), settings.embeds.clone())),
+                config.discord,
+            ));
+        }
+        if !config.telegram.token.is_empty() && !config.telegram.chat.is_empty() {
+            notifiers.push((
+                Arc::new(Telegram(
+                    config.telegram.token.clone(),
+                    config.telegram.chat.clone(),
+                )),
+                config.telegram.route,
+            ));
+        }
+        if !config.json.url.is_empty() {
+            notifiers.push((
+                Arc::new(JsonHook(config.json.url.clone())),
+                config.json.route,
+            ));
+        }
+        if !config.slack.url.is_empty() {
+            notifiers.push((
+                Arc::new(Slack(config.slack.url.clone())),
+                config.slack.route,
+            ));
+        }
+        if config.desktop.enabled {
+            notifiers.push((Arc::new(Desktop), config.desktop.route));
+        }
+
+        Notifiers(notifiers)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sends the report to every notifier routed for its event, returns the failed ones with
+    /// their errors
+    pub async fn notify(&self, report: &Report) -> Vec<(&'static str, String)> {
+        let sends = self
+            .0
+            .iter()
+            .filter(|(_, route)| route.allows(report.event))
+            .map(|(notifier, _)| async move {
+                notifier
+                    .send(report)
+                    .await
+                    .map_err(|err| (notifier.name(), err))
+            });
+
+        join_all(sends)
+            .await
+            .into_iter()
+            .filter_map(Result::err)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use api::misc::client;
+
+    use super::failure;
+
+    #[tokio::test]
+    async fn errors_hide_tokens() {
+        // Nothing listens there, the connection is refused
+        let url = "http://127.0.0.1:1/bot123:SECRET/sendMessage";
+        let err = client(None, None, false)
+            .post(url)
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("SECRET"));
+        assert!(!failure(err).contains("SECRET"));
+    }
+}
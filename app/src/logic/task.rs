@@ -1,109 +1,299 @@
 use std::{
-    fmt::Display,
+    fmt::{self, Display, Formatter},
+    fs::{create_dir_all, write},
     hash::{Hash, Hasher},
-    sync::Arc,
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use chrono::{offset::TimeZone, Local, NaiveDate, NaiveDateTime, Utc};
+use api::{
+    checkout::{
+        Checkout, CheckoutStep, Ctx, EndKind, Flow, Item, PriceGuard, RetryPolicy, Stage,
+        StepOutcome,
+    },
+    client::WildberriesClient,
+    error::{Error, ErrorKind},
+    log::{self, Level, Tag},
+    site::Site,
+    transcript::Transcript,
+};
+use async_trait::async_trait;
 use iced::{
-    button, Align, Button, Color, Column, Container, Element, Length, Row, Rule, Subscription, Text,
+    button, pick_list, Align, Button, Color, Column, Container, Element, Length, PickList, Row,
+    Rule, Subscription, Text,
 };
-use iced_futures::futures::stream;
+use iced_futures::futures::{stream, StreamExt};
 use iced_native::subscription::Recipe;
-use reqwest::{Client, StatusCode};
-use serde_json::{from_str, json};
-use tokio::time::sleep;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
     icons::{icon, Icon},
     layout::Message,
     logic::{
-        misc::RequestMethod,
-        models::{Basket, ProductCard, ResponseResult, ResponseValue, Webhook},
+        history::{self, Entry, Outcome},
+        logs,
+        notify::{Event, Notifiers, Report},
     },
     themes::Theme,
-    EDITION, SITE, VERSION,
-};
-
-use super::{
-    misc::{client, rand_millis, request, retrieve},
-    models::{Size, Variant},
+    EDITION, OS, REPORTS_DIR, VERSION,
 };
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Task
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// Number of log records shown in the expanded task
+const LOG_VIEW: usize = 20;
+
+#[derive(Deserialize, Serialize)]
 pub struct Task {
     pub uid: u64,
 
+    #[serde(skip)]
+    pub site: Site,
     pub proxy: Option<String>,
-    pub card: ProductCard,
-    pub variant: Variant,
-    pub size: Size,
+    /// Order lines, the first one names the task
+    pub items: Vec<Item>,
 
     pub account: (String, String),
-    pub webhook: Webhook,
+    #[serde(skip)]
+    pub notifiers: Notifiers,
     pub flags: (bool, bool, bool, u64),
+    pub retry: RetryPolicy,
+    pub guard: PriceGuard,
+    /// Drop time and warmup lead time
+    pub schedule: Option<(SystemTime, Duration)>,
+    /// Keep the requests of the tier which fails for a bug report
+    #[serde(default)]
+    pub capture: bool,
 
     pub progress: TaskProgress,
-
-    link: Arc<()>,
+    #[serde(skip)]
+    pub resume: Resume,
+
+    /// Shared with the background, which leaves the stopped checkout here to be resumed
+    #[serde(skip)]
+    link: Arc<Mutex<Option<Checkout>>>,
+    /// Notifiers which failed to deliver the result, shared with the notify step
+    #[serde(skip)]
+    delivery: Arc<Mutex<Option<String>>>,
+    /// Requests of the failed tier, shared with the background
+    #[serde(skip)]
+    transcript: Arc<Mutex<Option<Transcript>>>,
+    /// Path of the exported bug report or the export error
+    #[serde(skip)]
+    export: Option<String>,
+    #[serde(skip)]
     state: TaskState,
 }
 
 impl Task {
     pub fn new(
         uid: u64,
+        site: Site,
         proxy: Option<String>,
-        card: ProductCard,
-        variant: Variant,
-        size: Size,
+        items: Vec<Item>,
         account: (String, String),
-        webhook: Webhook,
+        notifiers: Notifiers,
         flags: (bool, bool, bool, u64),
+        retry: RetryPolicy,
+        guard: PriceGuard,
+        schedule: Option<(SystemTime, Duration)>,
     ) -> Task {
         Task {
             uid,
+            site,
             proxy,
-            card,
-            variant,
-            size,
+            items,
             account,
-            webhook,
+            notifiers,
             flags,
+            retry,
+            guard,
+            schedule,
+            capture: false,
             progress: TaskProgress::Start,
-            link: Arc::new(()),
+            resume: Resume::default(),
+            link: Arc::new(Mutex::new(None)),
+            delivery: Arc::new(Mutex::new(None)),
+            transcript: Arc::new(Mutex::new(None)),
+            export: None,
             state: TaskState::default(),
         }
     }
 
+    pub fn capture(mut self, enabled: bool) -> Task {
+        self.capture = enabled;
+        self
+    }
+
+    /// Prepares a task loaded from the tasks file. Unfinished tasks are started again or left idle
+    pub fn restore(&mut self, site: Site, notifiers: Notifiers, autostart: bool) {
+        self.site = site;
+        self.notifiers = notifiers;
+
+        if self.progress.is_running() || self.progress == TaskProgress::Idle {
+            self.progress = if autostart {
+                TaskProgress::Start
+            } else {
+                TaskProgress::Idle
+            };
+        }
+    }
+
     pub fn update(&mut self, msg: TaskMsg) {
         match msg {
             TaskMsg::State(state) => self.state = state,
-            TaskMsg::Retry => self.progress = TaskProgress::Start,
+            TaskMsg::Resume(resume) => self.resume = resume,
+            TaskMsg::Retry => {
+                if self.resume == Resume::Restart {
+                    *self.link.lock().unwrap() = None;
+                }
+
+                *self.delivery.lock().unwrap() = None;
+                *self.transcript.lock().unwrap() = None;
+                self.export = None;
+                self.progress = TaskProgress::Start;
+            }
+            TaskMsg::Stop => {
+                if self.progress.is_running() {
+                    self.progress = TaskProgress::Idle;
+                }
+            }
+            TaskMsg::Logs => {
+                if let TaskState::Expanded { ref mut logs, .. } = self.state {
+                    *logs = !*logs;
+                }
+            }
+            TaskMsg::Transcript => {
+                if let TaskState::Expanded {
+                    ref mut transcript, ..
+                } = self.state
+                {
+                    *transcript = !*transcript;
+                }
+            }
+            TaskMsg::Export => {
+                self.export = Some(match self.export() {
+                    Ok(path) => format!("Bug report saved to {}", path),
+                    Err(err) => format!("Can't save the bug report: {}", err),
+                })
+            }
             TaskMsg::Delete => (),
         }
     }
 
+    /// State of the task for the control API
+    pub fn summary(&self) -> serde_json::Value {
+        json!({
+            "id": self.uid,
+            "title": self.title(),
+            "items": self
+                .items
+                .iter()
+                .map(|i| json!({
+                    "cod": i.variant.id,
+                    "product": i.card.name,
+                    "variant": i.variant.name,
+                    "sizes": i.sizes_name(),
+                    "quantity": i.quantity,
+                }))
+                .collect::<Vec<serde_json::Value>>(),
+            "account": self.account.0,
+            "proxy": self.proxy,
+            "status": self.progress.to_str(),
+            "progress": self.progress,
+        })
+    }
+
+    /// Writes the transcript of the failed tier with the task and its log records to a bug report
+    /// file, returns its path
+    fn export(&self) -> io::Result<String> {
+        let transcript = self.transcript.lock().unwrap().clone();
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_secs())
+            .unwrap_or_default();
+        let report = json!({
+            "version": VERSION,
+            "edition": EDITION,
+            "os": OS,
+            "site": self.site.to_string(),
+            "task": self.summary(),
+            "transcript": transcript,
+            "logs": logs::task(self.uid, usize::MAX),
+        });
+
+        create_dir_all(REPORTS_DIR)?;
+        let path = Path::new(REPORTS_DIR).join(format!("task-{}-{}.json", self.uid, time));
+        write(&path, serde_json::to_string_pretty(&report)?)?;
+
+        Ok(path.display().to_string())
+    }
+
+    /// Product name of the first item, followed by the number of the other ones
+    fn title(&self) -> String {
+        match self.items.len() {
+            0 => String::from("-"),
+            1 => self.items[0].card.name.clone(),
+            n => format!("{} (+{})", self.items[0].card.name, n - 1),
+        }
+    }
+
     pub fn view(&mut self, theme: &Theme) -> Element<TaskMsg> {
+        let title = self.title();
+        let variants = self
+            .items
+            .iter()
+            .map(|i| match i.variant.name {
+                Some(ref name) => name.as_str(),
+                None => "-",
+            })
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let sizes = self
+            .items
+            .iter()
+            .map(|i| match i.quantity {
+                1 => i.sizes_name(),
+                n => format!("{} ×{}", i.sizes_name(), n),
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        let delivery = self.delivery.lock().unwrap().clone();
+        let failed = self.transcript.lock().unwrap().clone();
+
+        let mut status = Row::new()
+            .push(Text::new(self.progress.to_str()).color(self.progress.color()))
+            .width(Length::FillPortion(1))
+            .align_items(Align::Center)
+            .spacing(8);
+
+        if delivery.is_some() {
+            status = status.push(icon(Icon::Alert).color(Color::from_rgb(0.835, 0.0, 0.0)));
+        }
+
         match self.state {
             TaskState::Collapsed { ref mut expand_btn } => Container::new(
                 Row::new()
                     .push(Text::new(&format!("#{}", self.uid)).width(Length::Units(24)))
-                    .push(Text::new(&self.card.name).width(Length::FillPortion(2)))
-                    .push(
-                        Text::new(self.progress.to_str())
-                            .width(Length::FillPortion(1))
-                            .color(self.progress.color()),
-                    )
+                    .push(Text::new(&title).width(Length::FillPortion(2)))
+                    .push(status)
                     .push(
                         Button::new(expand_btn, icon(Icon::ArrowDown))
                             .on_press(TaskMsg::State(TaskState::Expanded {
                                 collapse_btn: button::State::new(),
+                                resume_pick: pick_list::State::default(),
                                 retry_btn: button::State::new(),
+                                logs_btn: button::State::new(),
                                 delete_btn: button::State::new(),
+                                transcript_btn: button::State::new(),
+                                export_btn: button::State::new(),
+                                logs: false,
+                                transcript: false,
                             }))
                             .width(Length::Shrink)
                             .padding(8)
@@ -117,8 +307,14 @@ impl Task {
             .into(),
             TaskState::Expanded {
                 ref mut collapse_btn,
+                ref mut resume_pick,
                 ref mut retry_btn,
+                ref mut logs_btn,
                 ref mut delete_btn,
+                ref mut transcript_btn,
+                ref mut export_btn,
+                logs,
+                transcript,
             } => {
                 let field = |name: &str, value: &str, fill: u16| {
                     Column::new()
@@ -131,126 +327,245 @@ impl Task {
                     .padding(8)
                     .style(theme.primary_btn());
 
-                if let TaskProgress::Error(_) = self.progress {
+                if let TaskProgress::Error(_) | TaskProgress::Failed(_) | TaskProgress::Idle =
+                    self.progress
+                {
                     retry_btn = retry_btn.on_press(TaskMsg::Retry);
                 }
 
-                Container::new(
-                    Column::new()
-                        .push(
-                            Row::new()
-                                .push(Text::new(&format!("#{}", self.uid)).width(Length::Units(24)))
-                                .push(Text::new(&self.card.name).width(Length::FillPortion(2)))
-                                .push(
-                                    Text::new(self.progress.to_str())
-                                        .width(Length::FillPortion(1))
-                                        .color(self.progress.color()),
-                                )
-                                .push(
-                                    Button::new(collapse_btn, icon(Icon::ArrowUp))
-                                        .on_press(TaskMsg::State(TaskState::Collapsed {
-                                            expand_btn: button::State::new(),
-                                        }))
-                                        .width(Length::Shrink)
-                                        .padding(8)
-                                        .style(theme.primary_btn()),
+                let mut content = Column::new()
+                    .push(
+                        Row::new()
+                            .push(Text::new(&format!("#{}", self.uid)).width(Length::Units(24)))
+                            .push(Text::new(&title).width(Length::FillPortion(2)))
+                            .push(status)
+                            .push(
+                                Button::new(collapse_btn, icon(Icon::ArrowUp))
+                                    .on_press(TaskMsg::State(TaskState::Collapsed {
+                                        expand_btn: button::State::new(),
+                                    }))
+                                    .width(Length::Shrink)
+                                    .padding(8)
+                                    .style(theme.primary_btn()),
+                            )
+                            .align_items(Align::Center)
+                            .padding(8)
+                            .spacing(8),
+                    )
+                    .push(
+                        Rule::horizontal(theme.task_divider_spacing()).style(theme.task_divider()),
+                    )
+                    .push(
+                        Row::new()
+                            .push(field("Variant (Color)", &variants, 3))
+                            .push(field("Size", &sizes, 2))
+                            .push(field("Account", &self.account.0, 3))
+                            .push(field(
+                                "Proxy",
+                                match self.proxy {
+                                    Some(ref proxy) => proxy,
+                                    None => "-",
+                                },
+                                4,
+                            ))
+                            .push(
+                                PickList::new(
+                                    resume_pick,
+                                    &Resume::ALL[..],
+                                    Some(self.resume),
+                                    TaskMsg::Resume,
                                 )
-                                .align_items(Align::Center)
-                                .padding(8)
-                                .spacing(8),
+                                .width(Length::Units(96)),
+                            )
+                            .push(retry_btn)
+                            .push(
+                                Button::new(logs_btn, icon(Icon::List))
+                                    .on_press(TaskMsg::Logs)
+                                    .width(Length::Shrink)
+                                    .padding(8)
+                                    .style(theme.primary_btn()),
+                            )
+                            .push(
+                                Button::new(delete_btn, icon(Icon::Delete))
+                                    .on_press(TaskMsg::Delete)
+                                    .width(Length::Shrink)
+                                    .padding(8)
+                                    .style(theme.danger_btn()),
+                            )
+                            .align_items(Align::Center)
+                            .padding(8)
+                            .spacing(8),
+                    );
+
+                if let Some(ref err) = delivery {
+                    content = content.push(
+                        Container::new(
+                            Text::new(format!("Notification not delivered ({})", err))
+                                .color(theme.color_text_muted())
+                                .size(15),
                         )
+                        .padding(8),
+                    );
+                }
+
+                if let Some(ref failed) = failed {
+                    let mut row = Row::new()
                         .push(
-                            Rule::horizontal(theme.task_divider_spacing())
-                                .style(theme.task_divider()),
+                            Text::new(format!("Requests of tier {} captured", failed.tier))
+                                .color(theme.color_text_muted())
+                                .size(15)
+                                .width(Length::Fill),
                         )
                         .push(
-                            Row::new()
-                                .push(field(
-                                    "Variant (Color)",
-                                    match self.variant.name {
-                                        Some(ref name) => name,
-                                        None => "-",
-                                    },
-                                    3,
-                                ))
-                                .push(field(
-                                    "Size",
-                                    if self.variant.sizes.len() == 1 {
-                                        "-"
-                                    } else {
-                                        &self.size.name
-                                    },
-                                    2,
-                                ))
-                                .push(field("Account", &self.account.0, 3))
-                                .push(field(
-                                    "Proxy",
-                                    match self.proxy {
-                                        Some(ref proxy) => proxy,
-                                        None => "-",
-                                    },
-                                    4,
-                                ))
-                                .push(retry_btn)
-                                .push(
-                                    Button::new(delete_btn, icon(Icon::Delete))
-                                        .on_press(TaskMsg::Delete)
-                                        .width(Length::Shrink)
-                                        .padding(8)
-                                        .style(theme.danger_btn()),
-                                )
-                                .align_items(Align::Center)
+                            Button::new(
+                                transcript_btn,
+                                Text::new(if transcript { "Hide" } else { "Show" }),
+                            )
+                            .on_press(TaskMsg::Transcript)
+                            .padding(8)
+                            .style(theme.primary_btn()),
+                        )
+                        .push(
+                            Button::new(export_btn, Text::new("Export bug report"))
+                                .on_press(TaskMsg::Export)
                                 .padding(8)
-                                .spacing(8),
-                        ),
-                )
-                .style(theme.card())
-                .into()
+                                .style(theme.primary_btn()),
+                        )
+                        .align_items(Align::Center)
+                        .padding(8)
+                        .spacing(8);
+
+                    if let Some(ref export) = self.export {
+                        row = row.push(Text::new(export).color(theme.color_text_muted()).size(15));
+                    }
+
+                    content = content.push(row);
+
+                    if transcript {
+                        content = content.push(
+                            Container::new(
+                                Text::new(failed.to_string())
+                                    .color(theme.color_text_muted())
+                                    .size(13),
+                            )
+                            .padding(8),
+                        );
+                    }
+                }
+
+                if logs {
+                    let records = logs::task(self.uid, LOG_VIEW);
+
+                    content = content.push(
+                        Container::new(if records.is_empty() {
+                            Column::new().push(
+                                Text::new("No records yet")
+                                    .color(theme.color_text_muted())
+                                    .size(13),
+                            )
+                        } else {
+                            records
+                                .iter()
+                                .fold(Column::new().spacing(2), |col, record| {
+                                    col.push(
+                                        Text::new(record.to_string())
+                                            .color(theme.color_text_muted())
+                                            .size(13),
+                                    )
+                                })
+                        })
+                        .padding(8),
+                    );
+                }
+
+                Container::new(content).style(theme.card()).into()
             }
         }
     }
 
+    fn ctx(&self) -> Ctx {
+        let ctx = Ctx::new(
+            WildberriesClient::new(
+                self.site.clone(),
+                self.proxy.clone(),
+                Some(self.account.1.as_str()),
+            )
+            .task(self.uid)
+            .capture(self.capture),
+            self.items.clone(),
+            self.flags,
+        )
+        .guard(self.guard);
+
+        match self.schedule {
+            Some((drop, lead)) => ctx.schedule(drop, lead),
+            None => ctx,
+        }
+    }
+
+    fn background(&self) -> BackgroundState {
+        BackgroundState {
+            checkout: Checkout::new(
+                Flow::checkout()
+                    .stage(
+                        Stage::End,
+                        vec![
+                            Box::new(Record {
+                                uid: self.uid,
+                                phone: self.account.0.clone(),
+                            }),
+                            Box::new(Notify {
+                                uid: self.uid,
+                                phone: self.account.0.clone(),
+                                notifiers: self.notifiers.clone(),
+                                delivery: self.delivery.clone(),
+                            }),
+                        ],
+                    )
+                    .retry(self.retry),
+                self.ctx(),
+            ),
+            progress: TaskProgress::Start,
+            resume: self.resume.stage(),
+            uid: self.uid,
+            phone: self.account.0.clone(),
+            notifiers: self.notifiers.clone(),
+            stopped: false,
+            link: self.link.clone(),
+            transcript: self.transcript.clone(),
+        }
+    }
+
     pub fn subscription(&self) -> Subscription<Message> {
-        match self.progress {
-            TaskProgress::Start
-            | TaskProgress::WarmingUp
-            | TaskProgress::Waiting(_)
-            | TaskProgress::Processing
-            | TaskProgress::Completing(_) => Subscription::from_recipe(Background {
+        match self.progress.is_running() {
+            true => Subscription::from_recipe(Background {
                 uid: self.uid,
-                state: BackgroundState {
-                    card: self.card.clone(),
-                    variant: self.variant.clone(),
-                    size: self.size.clone(),
-                    phone: self.account.0.clone(),
-                    webhook: self.webhook.clone(),
-                    flags: self.flags.clone(),
-                    client: client(
-                        self.proxy.clone(),
-                        Some(&[(
-                            String::from("WILDAUTHNEW_V3"),
-                            self.account.1.clone(),
-                            String::from("wildberries.ru"),
-                        )]),
-                    ),
-                    progress: TaskProgress::Start,
-                    step: BackgroundStep::Start,
-                    substep: 0,
-                    start: SystemTime::now(),
-                    stopped: false,
-                    link: self.link.clone(),
-                },
+                state: self.background(),
             })
             .map(Message::TaskProgressed),
-            _ => Subscription::none(),
+            false => Subscription::none(),
         }
     }
+
+    /// Progress of the checkout run outside of the application, ends once the task stops
+    pub fn run(&self) -> stream::BoxStream<'static, TaskProgress> {
+        self.background().run()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub enum TaskMsg {
     State(TaskState),
+    Resume(Resume),
     Retry,
+    /// Leaves the running task idle, the checkout is dropped
+    Stop,
+    /// Shows or hides the log records of the expanded task
+    Logs,
+    /// Shows or hides the requests of the failed tier
+    Transcript,
+    /// Saves the failed tier requests with the task logs as a bug report
+    Export,
     Delete,
 }
 
@@ -261,8 +576,14 @@ pub enum TaskState {
     },
     Expanded {
         collapse_btn: button::State,
+        resume_pick: pick_list::State<Resume>,
         retry_btn: button::State,
+        logs_btn: button::State,
         delete_btn: button::State,
+        transcript_btn: button::State,
+        export_btn: button::State,
+        logs: bool,
+        transcript: bool,
     },
 }
 
@@ -274,17 +595,58 @@ impl Default for TaskState {
     }
 }
 
+/// Where a retried task continues from
+#[derive(Eq, PartialEq, Copy, Clone, Default, Debug)]
+pub enum Resume {
+    /// Monitor the product again
+    Waiting,
+    /// Go on with the cart already filled
+    Process,
+    #[default]
+    Restart,
+}
+
+impl Resume {
+    pub const ALL: [Resume; 3] = [Resume::Waiting, Resume::Process, Resume::Restart];
+
+    fn stage(&self) -> Option<Stage> {
+        match self {
+            Resume::Waiting => Some(Stage::Waiting),
+            Resume::Process => Some(Stage::Process),
+            Resume::Restart => None,
+        }
+    }
+}
+
+impl Display for Resume {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Resume::Waiting => "Waiting",
+                Resume::Process => "Process",
+                Resume::Restart => "Restart",
+            }
+        )
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Task Progress
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Deserialize, Serialize, PartialEq, Clone, Debug)]
 pub enum TaskProgress {
+    /// Restored, waits to be started by the user
+    Idle,
     Start,
     WarmingUp,
     Waiting(Option<String>),
     Processing,
     Completing(Option<String>),
+    /// (retry, max retries, error)
+    Retrying(u32, u32, String),
 
     Error(String),
     Complete(Option<String>),
@@ -292,8 +654,47 @@ pub enum TaskProgress {
 }
 
 impl TaskProgress {
-    fn to_str(&self) -> String {
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self,
+            TaskProgress::Start
+                | TaskProgress::WarmingUp
+                | TaskProgress::Waiting(_)
+                | TaskProgress::Processing
+                | TaskProgress::Completing(_)
+                | TaskProgress::Retrying(..)
+        )
+    }
+
+    fn from_stage(stage: Stage, msg: Option<String>) -> TaskProgress {
+        match stage {
+            Stage::Start => TaskProgress::Start,
+            Stage::Scheduled => TaskProgress::Waiting(msg),
+            Stage::Warmup => TaskProgress::WarmingUp,
+            Stage::Waiting => TaskProgress::Waiting(msg),
+            Stage::Process => TaskProgress::Processing,
+            Stage::End => TaskProgress::Completing(msg),
+        }
+    }
+
+    /// Progress event of the task, as streamed by the headless mode and the control API
+    pub fn event(&self, uid: u64) -> serde_json::Value {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_millis() as u64)
+            .unwrap_or_default();
+
+        json!({
+            "time": time,
+            "task": uid,
+            "status": self.to_str(),
+            "progress": self,
+        })
+    }
+
+    pub fn to_str(&self) -> String {
         match self {
+            TaskProgress::Idle => String::from("Idle"),
             TaskProgress::Start => String::from("Starting"),
             TaskProgress::WarmingUp => String::from("Warming Up"),
             TaskProgress::Waiting(msg) => match msg {
@@ -305,6 +706,9 @@ impl TaskProgress {
                 Some(ref text) => format!("Completing: {}", text.clone()),
                 None => String::from("Completing"),
             },
+            TaskProgress::Retrying(attempt, attempts, msg) => {
+                format!("Retrying ({}/{}): {}", attempt, attempts, msg)
+            }
             TaskProgress::Complete(msg) => match msg {
                 Some(ref text) => format!("Complete: {}", text),
                 None => String::from("Complete"),
@@ -325,11 +729,12 @@ impl TaskProgress {
 
     fn color(&self) -> Color {
         match *self {
-            TaskProgress::Start => Color::BLACK,
+            TaskProgress::Idle | TaskProgress::Start => Color::BLACK,
             TaskProgress::WarmingUp => Color::from_rgb(1.0, 0.671, 0.0),
             TaskProgress::Waiting(_) => Color::from_rgb(0.188, 0.31, 0.996),
             TaskProgress::Processing => Color::from_rgb(0.867, 0.173, 0.0),
             TaskProgress::Completing(_) => Color::from_rgb(0.0, 0.784, 0.325),
+            TaskProgress::Retrying(..) => Color::from_rgb(0.937, 0.424, 0.0),
             TaskProgress::Complete(_) => Color::from_rgb(0.392, 0.867, 0.09),
             TaskProgress::Failed(_) | TaskProgress::Error(_) => Color::from_rgb(0.835, 0.0, 0.0),
         }
@@ -342,22 +747,6 @@ impl Default for TaskProgress {
     }
 }
 
-enum TaskError {
-    Response,
-    Scheme,
-    Unknown,
-}
-
-impl TaskError {
-    pub fn to_string(&self, tier: &str) -> String {
-        match &self {
-            TaskError::Response => format!("Bad response ({})", tier),
-            TaskError::Scheme => format!("Unknown scheme ({})", tier),
-            TaskError::Unknown => format!("Unknown ({})", tier),
-        }
-    }
-}
-
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Task Background
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -384,892 +773,240 @@ where
     fn stream(self: Box<Self>, _input: stream::BoxStream<I>) -> stream::BoxStream<Self::Output> {
         let uid = self.uid;
 
-        Box::pin(stream::unfold(self.state, move |mut state| async move {
+        Box::pin(self.state.run().map(move |progress| (uid, progress)))
+    }
+}
+
+struct BackgroundState {
+    checkout: Checkout,
+    progress: TaskProgress,
+    /// Stage to resume the saved checkout from, a fresh one is run otherwise
+    resume: Option<Stage>,
+
+    /// Task id and account phone for the error reports
+    uid: u64,
+    phone: String,
+    notifiers: Notifiers,
+
+    stopped: bool,
+    link: Arc<Mutex<Option<Checkout>>>,
+    transcript: Arc<Mutex<Option<Transcript>>>,
+}
+
+impl BackgroundState {
+    fn run(self) -> stream::BoxStream<'static, TaskProgress> {
+        Box::pin(stream::unfold(self, |mut state| async move {
             if state.stopped {
+                *state.link.lock().unwrap() = Some(state.checkout);
                 return None;
             }
 
+            if let Some(stage) = state.resume.take() {
+                let saved = state.link.lock().unwrap().take();
+
+                if let Some(mut checkout) = saved {
+                    checkout.resume(stage);
+                    state.checkout = checkout;
+                    state.progress = TaskProgress::from_stage(stage, None);
+                }
+            }
+
             loop {
                 if Arc::strong_count(&state.link) == 1 {
-                    println!("Task dropped");
+                    log::Record::new(Level::Info, Tag::task(state.uid), "Task dropped").log();
                     return None;
                 }
 
-                let mut action = LoopAction::Continue;
+                let action = state.checkout.step().await;
 
-                match state.step {
-                    BackgroundStep::Start => {
-                        action = LoopAction::Move(BackgroundStep::Warmup, None);
+                match action {
+                    StepOutcome::Continue => {
+                        if let TaskProgress::Retrying(..) = state.progress {
+                            state.progress = TaskProgress::from_stage(state.checkout.stage(), None)
+                        }
                     }
-                    BackgroundStep::Warmup => match state.substep {
-                        // Token check (A)
-                        0 => match request(
-                            &mut state.client,
-                            "https://www.wildberries.ru/lk/personalcabinet/data",
-                            RequestMethod::GET,
-                            "https://www.wildberries.ru/lk",
-                            0,
-                        )
-                        .await
-                        {
-                            Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                Ok(result) => {
-                                    if result.state == -1 {
-                                        action = LoopAction::Error(String::from(
-                                            "Account token is expired",
-                                        ))
-                                    }
-                                }
-                                Err(_) => {
-                                    action = LoopAction::Error(TaskError::Response.to_string("A"))
-                                }
-                            },
-                            Err(err) => action = LoopAction::Error(err.to_string("A")),
-                        },
-                        // User location cookie (B)
-                        1 => match request(
-                            &mut state.client,
-                            "https://www.wildberries.ru/geo/getuserlocationinfo",
-                            RequestMethod::POST(None),
-                            "https://www.wildberries.ru/login?returnUrl=https://wildberries.ru/",
-                            if state.flags.0 {
-                                rand_millis(5..=10)
-                            } else {
-                                0
-                            },
-                        )
-                        .await
-                        {
-                            Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                Ok(result) => {
-                                    if result.state == -1 {
-                                        action = LoopAction::Error(String::from(
-                                            "Can't get user location (B)",
-                                        ))
-                                    }
-                                }
-                                Err(_) => {
-                                    action = LoopAction::Error(TaskError::Response.to_string("B"))
-                                }
-                            },
-                            Err(err) => action = LoopAction::Error(err.to_string("B")),
-                        },
-                        // Check cart for other products (C)
-                        2 => {
-                            match request(
-                                &mut state.client,
-                                "https://www.wildberries.ru/lk/basket/data",
-                                RequestMethod::GET,
-                                "https://www.wildberries.ru/lk/basket",
-                                if state.flags.0 {
-                                    rand_millis(10..=20)
-                                } else {
-                                    0
-                                },
-                            )
-                            .await
-                            {
-                                Ok(resp) => {
-                                    match from_str::<ResponseResult>(&resp.body) {
-                                        Ok(result) => {
-                                            if result.state == 0 {
-                                                if let ResponseValue::Value(value) = result.value {
-                                                    match value.data.basket {
-                                                        Some(basket) => {
-                                                            if !basket.order_items.is_empty() {
-                                                                // Clear cart (D)
-                                                                match request(
-                                                                &mut state.client,
-                                                                "https://www.wildberries.ru/lk/basket/spa/delete",
-                                                                RequestMethod::POST(
-                                                                    Some(
-                                                                        &basket.order_items.iter()
-                                                                            .enumerate()
-                                                                            .map(|(i, p)| {
-                                                                                (format!("chrtIds[{}]", i), p.to_string())
-                                                                            })
-                                                                            .collect()
-                                                                    )
-                                                                ),
-                                                                "https://www.wildberries.ru/lk/basket",
-                                                                if state.flags.0 {
-                                                                    rand_millis(5..=10)
-                                                                } else {
-                                                                    0
-                                                                },
-                                                            ).await {
-                                                                Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                                                    Ok(result) => if result.state == -1 {
-                                                                        action =
-                                                                            LoopAction::Error(
-                                                                                String::from("Can't remove other items from cart")
-                                                                            )
-                                                                    }
-                                                                    Err(_) => action = LoopAction::Error(
-                                                                        TaskError::Response.to_string("D")
-                                                                    ),
-                                                                },
-                                                                Err(err) => action = LoopAction::Error(
-                                                                    err.to_string("D")
-                                                                )
-                                                            }
-                                                            }
-                                                        }
-                                                        None => {
-                                                            action = LoopAction::Error(
-                                                                TaskError::Scheme.to_string("C/B"),
-                                                            )
-                                                        }
-                                                    }
-                                                }
-                                            } else {
-                                                action = LoopAction::Error(String::from(
-                                                    "Can't retrieve cart data",
-                                                ))
-                                            }
-                                        }
-                                        Err(_) => {
-                                            action = LoopAction::Error(
-                                                TaskError::Response.to_string("C"),
-                                            )
-                                        }
-                                    }
-
-                                    match action {
-                                        LoopAction::Error(_) if !state.flags.1 => {}
-                                        LoopAction::Continue | LoopAction::Error(_) => {
-                                            action = LoopAction::Move(
-                                                BackgroundStep::Waiting,
-                                                Some(String::from("Scanning")),
-                                            )
-                                        }
-                                        _ => {}
-                                    }
-                                }
-                                Err(err) => action = LoopAction::Error(err.to_string("C")),
-                            }
+                    StepOutcome::Break(ref msg) => match state.progress {
+                        TaskProgress::Waiting(_)
+                        | TaskProgress::Completing(_)
+                        | TaskProgress::Retrying(..) => {
+                            state.progress =
+                                TaskProgress::from_stage(state.checkout.stage(), msg.clone())
                         }
                         _ => {}
                     },
-                    BackgroundStep::Waiting => {
-                        match state.substep {
-                            // Check product availability (E)
-                            0 => match request(
-                                &mut state.client,
-                                &format!(
-                                    "https://www.wildberries.ru/{}/product/data?targetUrl=XS",
-                                    state.variant.id,
-                                ),
-                                RequestMethod::GET,
-                                &format!(
-                                    "https://www.wildberries.ru/catalog/{}/detail.aspx?targetUrl=XS",
-                                    state.variant.id
-                                ),
-                                if state.flags.0 {
-                                    rand_millis(25..=30)
-                                } else {
-                                    0
-                                },
-                            )
-                                .await
-                            {
-                                Ok(resp) => {
-                                    if resp.status == StatusCode::NOT_FOUND {
-                                        action =
-                                            LoopAction::Error(String::from("Product not found"));
-                                    } else {
-                                        match from_str::<ResponseResult>(&resp.body) {
-                                            Ok(result) => {
-                                                if let ResponseValue::Value(value) = result.value {
-                                                    match value.data.variant {
-                                                        Some(variant) => {
-                                                            match variant
-                                                                .sizes
-                                                                .get(&state.size.id.to_string())
-                                                            {
-                                                                Some(size) => {
-                                                                    if size.sold_out {
-                                                                        if state.flags.2 {
-                                                                            action = LoopAction::Break(None);
-                                                                            state.substep = 1;
-                                                                        } else {
-                                                                            action = LoopAction::Error(
-                                                                                String::from(
-                                                                                    "Size is sold out",
-                                                                                ),
-                                                                            );
-                                                                        }
-                                                                    } else {
-                                                                        action = LoopAction::Move(
-                                                                            BackgroundStep::Process {
-                                                                                cart: Basket::default(),
-                                                                            },
-                                                                            None,
-                                                                        );
-                                                                    }
-                                                                }
-                                                                None => action = LoopAction::Error(
-                                                                    String::from(
-                                                                        "Size not found",
-                                                                    )
-                                                                ),
-                                                            }
-                                                        }
-                                                        None => {
-                                                            action = LoopAction::Error(
-                                                                TaskError::Scheme
-                                                                    .to_string("E/VRT"),
-                                                            )
-                                                        }
-                                                    }
-                                                } else {
-                                                    action = LoopAction::Error(
-                                                        TaskError::Scheme.to_string("E/V"),
-                                                    );
-                                                }
-                                            }
-                                            Err(_) => {
-                                                action = LoopAction::Error(
-                                                    TaskError::Response.to_string("E"),
-                                                )
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(err) => action = LoopAction::Error(err.to_string("E")),
-                            },
-                            1 => {
-                                sleep(Duration::from_millis(state.flags.3)).await;
-                                action = LoopAction::Break(Some(String::from("Scanning")));
-                                state.substep = 0;
-                            }
-                            _ => {}
-                        }
+                    StepOutcome::Wait(ref msg) => {
+                        state.progress =
+                            TaskProgress::from_stage(state.checkout.stage(), Some(msg.clone()));
                     }
-                    BackgroundStep::Process { ref mut cart } => {
-                        match state.substep {
-                            // Add product to cart (F)
-                            0 => match request(
-                                &mut state.client,
-                                "https://www.wildberries.ru/product/addtobasket",
-                                RequestMethod::POST(Some(&vec![
-                                    (String::from("cod1S"), state.variant.id.to_string()),
-                                    (String::from("characteristicId"), state.size.id.to_string()),
-                                    (String::from("quantity"), String::from("1")),
-                                ])),
-                                &format!(
-                                "https://www.wildberries.ru/catalog/{}/detail.aspx?targetUrl=XS",
-                                state.variant.id
-                            ),
-                                if state.flags.0 {
-                                    rand_millis(25..=30)
-                                } else {
-                                    0
-                                },
-                            )
-                            .await
-                            {
-                                Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                    Ok(result) => {
-                                        if result.state == -1 {
-                                            action = LoopAction::Error(
-                                                TaskError::Unknown.to_string("F"),
-                                            );
-                                        } else {
-                                            if let ResponseValue::Basket(data) = result.value {
-                                                match data.basket_info {
-                                                    Some(basket_short) => {
-                                                        match basket_short.quantity {
-                                                            0 => {
-                                                                action =
-                                                                    LoopAction::Error(String::from(
-                                                                        "Can't add product to cart",
-                                                                    ))
-                                                            }
-                                                            1 => {}
-                                                            _ => {
-                                                                if !state.flags.1 {
-                                                                    action = LoopAction::Error(String::from("Cart corrupted. Check it by yourself"))
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    None => {
-                                                        action = LoopAction::Error(
-                                                            TaskError::Scheme.to_string("F/BS"),
-                                                        )
-                                                    }
-                                                }
-                                            } else {
-                                                action = LoopAction::Error(
-                                                    TaskError::Scheme.to_string("F/V"),
-                                                );
-                                            }
-                                        }
-                                    }
-                                    Err(_) => {
-                                        action =
-                                            LoopAction::Error(TaskError::Response.to_string("F"))
-                                    }
-                                },
-                                Err(err) => action = LoopAction::Error(err.to_string("F")),
-                            },
-                            // Collect final cart data (G)
-                            1 => match request(
-                                &mut state.client,
-                                "https://www.wildberries.ru/lk/basket/data",
-                                RequestMethod::GET,
-                                "https://www.wildberries.ru/lk/basket",
-                                if state.flags.0 {
-                                    rand_millis(15..=25)
-                                } else {
-                                    0
-                                },
-                            )
-                            .await
-                            {
-                                Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                    Ok(result) => {
-                                        if result.state == 0 {
-                                            if let ResponseValue::Value(value) = result.value {
-                                                match value.data.basket {
-                                                    Some(data) => *cart = data,
-                                                    None => {
-                                                        action = LoopAction::Error(
-                                                            TaskError::Scheme.to_string("G/B"),
-                                                        )
-                                                    }
-                                                }
-                                            } else {
-                                                action = LoopAction::Error(
-                                                    TaskError::Scheme.to_string("G/V"),
-                                                );
-                                            }
-                                        } else {
-                                            action = LoopAction::Error(String::from(
-                                                "Can't retrieve cart data",
-                                            ));
-                                        }
-                                    }
-                                    Err(_) => {
-                                        action =
-                                            LoopAction::Error(TaskError::Response.to_string("G"))
-                                    }
-                                },
-                                Err(err) => action = LoopAction::Error(err.to_string("G")),
-                            },
-                            // Submit order (H)
-                            2 => {
-                                let mut form = vec![
-                                    (
-                                        String::from("orderDetails.DeliveryPointId"),
-                                        cart.delivery_point.id.to_string(),
-                                    ),
-                                    (
-                                        String::from("orderDetails.DeliveryWay"),
-                                        cart.delivery_way.clone(),
-                                    ),
-                                    (String::from("orderDetails.DeliveryPrice"), String::new()),
-                                ];
-
-                                {
-                                    cart.delivery_ways[0].calendars.iter().enumerate().for_each(|(cid, c)| {
-                                    form.push((String::from("orderDetails.DeliveryDts.Index"), cid.to_string()));
-                                    form.push((
-                                        format!("orderDetails.DeliveryDts[{}].Date", cid),
-                                        NaiveDate::parse_from_str(&c.shipping_interval.delivery_date, "%-m/%-d/%Y").unwrap().format("%d.%m.%Y").to_string(),
-                                    ));
-                                    form.push((String::from("orderDetails.DeliveryDts[0].IntervalId"), c.shipping_interval.id.to_string()));
-
-                                    c.store_ids.iter().enumerate().for_each(|(sid, s)| {
-                                        form.push((String::from("orderDetails.DeliveryDts[0].StoreIds.Index"), sid.to_string()));
-                                        form.push((format!("orderDetails.DeliveryDts[0].StoreIds[{}]", sid), s.to_string()));
-                                    });
-                                });
-
-                                    form.push((
-                                        String::from("orderDetails.GooglePayToken"),
-                                        false.to_string(),
-                                    ));
-                                    form.push((
-                                        String::from("orderDetails.PaymentType.Id"),
-                                        cart.payment_type.id.to_string(),
-                                    ));
-                                    form.push((
-                                        String::from("orderDetails.MaskedCardId"),
-                                        cart.payment_type.card.clone(),
-                                    ));
-                                    form.push((
-                                        String::from("orderDetails.SberPayPhone"),
-                                        String::new(),
-                                    ));
-                                    form.push((
-                                        String::from("orderDetails.AgreePublicOffert"),
-                                        true.to_string(),
-                                    ));
-                                    form.push((
-                                        String::from("orderDetails.TotalPrice"),
-                                        cart.total_price.to_string(),
-                                    ));
-
-                                    cart.order_items.iter().enumerate().for_each(|(id, i)| {
-                                        form.push((
-                                            String::from("orderDetails.UserBasketItems.Index"),
-                                            id.to_string(),
-                                        ));
-                                        form.push((
-                                            format!(
-                                                "orderDetails.UserBasketItems[{}].CharacteristicId",
-                                                id
-                                            ),
-                                            i.to_string(),
-                                        ));
-                                        form.push((
-                                            format!("orderDetails.IncludeInOrder[{}]", id),
-                                            i.to_string(),
-                                        ));
-                                    });
-                                }
-
-                                match request(
-                                    &mut state.client,
-                                    "https://www.wildberries.ru/lk/basket/spa/submitorder",
-                                    RequestMethod::POST(Some(&form)),
-                                    "https://www.wildberries.ru/lk/basket",
-                                    if state.flags.0 {
-                                        rand_millis(15..=20)
-                                    } else {
-                                        0
-                                    },
-                                )
-                                .await
-                                {
-                                    Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                        Ok(result) => {
-                                            if result.state == -1 {
-                                                action = LoopAction::Error(
-                                                    TaskError::Unknown.to_string("H"),
-                                                );
-                                            } else {
-                                                if let ResponseValue::Order { url } = result.value {
-                                                    if url.ends_with("payment/fail") {
-                                                    } else if url
-                                                        .starts_with("https://beta.paywb.com")
-                                                    {
-                                                        action = LoopAction::Move(
-                                                            BackgroundStep::End {
-                                                                content: url,
-                                                                cart: cart.clone(),
-                                                                kind: EndKind::UserAction,
-                                                            },
-                                                            None,
-                                                        )
-                                                    } else if url.contains("orderId") {
-                                                        // Confirm payment success (J)
-                                                        action = LoopAction::Move(
-                                                            BackgroundStep::End {
-                                                                content: url.clone(),
-                                                                cart: cart.clone(),
-                                                                kind: EndKind::Succeed(match retrieve(&url, "?orderId=", "&paid") {
-                                                                    Some(oid) => match request(
-                                                                        &mut state.client,
-                                                                        &format!("https://www.wildberries.ru/lk/order/confirmed/data?orderId={}&paid=True", oid),
-                                                                        RequestMethod::GET,
-                                                                        "https://www.wildberries.ru/lk/basket",
-                                                                        if state.flags.0 {
-                                                                            rand_millis(10..=15)
-                                                                        } else {
-                                                                            0
-                                                                        },
-                                                                    ).await {
-                                                                        Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                                                            Ok(result) => result.state == 0,
-                                                                            Err(_) => false,
-                                                                        },
-                                                                        Err(_) => false,
-                                                                    }
-                                                                    None => false,
-                                                                })
-                                                            },
-                                                            Some(String::from("Sending embed")),
-                                                        )
-                                                    } else {
-                                                        action = LoopAction::Error(
-                                                            TaskError::Scheme.to_string("H/URL"),
-                                                        );
-                                                    }
-                                                } else {
-                                                    action = LoopAction::Error(
-                                                        TaskError::Scheme.to_string("H/V"),
-                                                    );
-                                                }
-                                            }
-                                        }
-                                        Err(_) => {
-                                            action = LoopAction::Error(
-                                                TaskError::Response.to_string("H"),
-                                            )
-                                        }
-                                    },
-                                    Err(err) => action = LoopAction::Error(err.to_string("H")),
-                                }
-                            }
-                            // Parse payment error (I)
-                            3 => match request(
-                                &mut state.client,
-                                "https://www.wildberries.ru/lk/payment/fail",
-                                RequestMethod::GET,
-                                "https://www.wildberries.ru/lk/basket",
-                                if state.flags.0 {
-                                    rand_millis(10..=15)
-                                } else {
-                                    0
-                                },
-                            )
-                            .await
-                            {
-                                Ok(resp) => {
-                                    action = LoopAction::Move(
-                                        BackgroundStep::End {
-                                            content: match retrieve(
-                                                &resp.body,
-                                                r#"<p class="field-validation-error">"#,
-                                                "</p>",
-                                            ) {
-                                                Some(desc) => desc,
-                                                None => String::from("<Can't parse error>"),
-                                            },
-                                            cart: cart.clone(),
-                                            kind: EndKind::Failed,
-                                        },
-                                        None,
-                                    )
-                                }
-                                Err(err) => action = LoopAction::Error(err.to_string("I")),
-                            },
-                            _ => {}
-                        }
+                    StepOutcome::Move(stage, ref msg) => {
+                        state.progress = TaskProgress::from_stage(stage, msg.clone());
                     }
-                    BackgroundStep::End {
-                        ref content,
-                        ref cart,
-                        ref kind,
-                    } => {
-                        let start_time = match state.start.duration_since(UNIX_EPOCH) {
-                            Ok(dur) => Utc
-                                .from_utc_datetime(&NaiveDateTime::from_timestamp(
-                                    dur.as_secs() as i64,
-                                    dur.subsec_nanos(),
-                                ))
-                                .with_timezone(&Local)
-                                .format("%H:%M:%S %d/%m/%Y")
-                                .to_string(),
-                            Err(_) => String::from("-"),
-                        };
-
-                        let fields = match kind {
-                            EndKind::Succeed(_) => json!([
-                                {
-                                    "name": "Order details",
-                                    "value": format!("[Click](https://www.wildberries.ru{})", content),
-                                },
-                                {
-                                    "name": "Task ID",
-                                    "value": format!("#{}", uid),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Account",
-                                    "value": format!("||{}||", &state.phone),
-                                    "inline": true,
-                                },
-                                { "name": "Site", "value": SITE, "inline": true },
-                                { "name": "Product", "value": state.card.name },
-                                {
-                                    "name": "Variant (Color)",
-                                    "value": state.variant.name,
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Size",
-                                    "value": if state.variant.sizes.len() == 1 {
-                                        "-"
-                                    } else {
-                                        &state.size.name
-                                    },
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Total",
-                                    "value": format!("{} RUB", cart.total_price),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Estimated delivery",
-                                    "value": cart.delivery_interval_str,
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Elapsed",
-                                    "value": format!(
-                                        "{:.3} sec",
-                                        state.start.elapsed().unwrap().as_secs_f32()
-                                    ),
-                                    "inline": true,
-                                },
-                                { "name": "Start Time", "value": start_time, "inline": true },
-                            ]),
-                            EndKind::UserAction => json!([
-                                {
-                                    "name": "Payment confirmation",
-                                    "value": format!("[Click]({})", content),
-                                },
-                                {
-                                    "name": "Task ID",
-                                    "value": format!("#{}", uid),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Account",
-                                    "value": format!("||{}||", &state.phone),
-                                    "inline": true,
-                                },
-                                { "name": "Site", "value": SITE, "inline": true },
-                                { "name": "Product", "value": state.card.name },
-                                {
-                                    "name": "Variant (Color)",
-                                    "value": state.variant.name,
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Size",
-                                    "value": if state.variant.sizes.len() == 1 {
-                                        "-"
-                                    } else {
-                                        &state.size.name
-                                    },
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Amount of payment",
-                                    "value": format!("{} RUB", cart.total_price),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Estimated delivery",
-                                    "value": cart.delivery_interval_str,
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Elapsed",
-                                    "value": format!(
-                                        "{:.3} sec",
-                                        state.start.elapsed().unwrap().as_secs_f32()
-                                    ),
-                                    "inline": true,
-                                },
-                                { "name": "Start Time", "value": start_time, "inline": true },
-                            ]),
-                            EndKind::Failed => json!([
-                                { "name": "Description", "value": content },
-                                {
-                                    "name": "Task ID",
-                                    "value": format!("#{}", uid),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Account",
-                                    "value": format!("||{}||", &state.phone),
-                                    "inline": true,
-                                },
-                                { "name": "Site", "value": SITE, "inline": true },
-                                { "name": "Product", "value": state.card.name },
-                                {
-                                    "name": "Variant (Color)",
-                                    "value": state.variant.name,
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Size",
-                                    "value": if state.variant.sizes.len() == 1 {
-                                        "-"
-                                    } else {
-                                        &state.size.name
-                                    },
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Total",
-                                    "value": format!("{} RUB", cart.total_price),
-                                    "inline": true,
-                                },
-                                {
-                                    "name": "Elapsed",
-                                    "value": format!(
-                                        "{:.3} sec",
-                                        state.start.elapsed().unwrap().as_secs_f32()
-                                    ),
-                                    "inline": true,
-                                },
-                                { "name": "Start Time", "value": start_time, "inline": true },
-                            ]),
-                        };
-
-                        let resp = client(None, None)
-                            .post(format!("https://discord.com/api/webhooks/{}/{}", state.webhook.id, state.webhook.token))
-                            .json(&json!({
-                            "username": "SDP Pre-Alpha",
-                            "embeds": [{
-                                "title": match kind {
-                                    EndKind::Succeed(true) => "Successful Payment",
-                                    EndKind::Succeed(false) => "Successful Payment (Unconfirmed)",
-                                    EndKind::UserAction => "Bank Payment Confirmation (3D-Secure)",
-                                    EndKind::Failed => "Payment Failed",
-                                },
-                                "description": match kind {
-                                    EndKind::Succeed(_) => "Click the link below to see details of your order.",
-                                    EndKind::UserAction => "User action required. Click the link below to complete your order payment.",
-                                    EndKind::Failed => "",
-                                },
-                                "color": match kind {
-                                    EndKind::Succeed(true) => 51283,
-                                    EndKind::Succeed(false) => 2712319,
-                                    EndKind::UserAction => 16771584,
-                                    EndKind::Failed => 16717636,
-                                },
-                                "fields": &fields,
-                                "footer": {
-                                    "text": format!("SDP Pre-Alpha (v{}) (e{})", VERSION, EDITION),
-                                    "icon_url": "https://en.gravatar.com/userimage/182691345/3ce2e13566d08dd3ae6513f6b0404900.png"
-                                }
-                            }]
-                        }))
-                            .send()
-                            .await
-                            .unwrap();
-
-                        if resp.status() == StatusCode::NO_CONTENT {
-                            action = match kind {
-                                EndKind::Succeed(confirm) => {
-                                    LoopAction::Complete(Some(String::from(if *confirm {
-                                        "Success"
-                                    } else {
-                                        "Success (Unconfirmed)"
-                                    })))
-                                }
-                                EndKind::UserAction => {
-                                    LoopAction::Complete(Some(String::from("User Action Required")))
-                                }
-                                EndKind::Failed => {
-                                    LoopAction::Failed(Some(String::from("Bank error")))
-                                }
-                            }
-                        } else {
-                            action = LoopAction::Failed(Some(String::from("Webhook error")));
-                        }
+                    StepOutcome::Retry(ref err) => {
+                        let (attempt, attempts) = state.checkout.attempt();
+                        state.progress = TaskProgress::Retrying(attempt, attempts, err.to_string());
                     }
-                }
-                match action {
-                    LoopAction::Continue => state.substep += 1,
-                    LoopAction::Break(ref msg) => {
-                        let cloned = msg.clone();
-
-                        match state.progress {
-                            TaskProgress::Waiting(_) => {
-                                state.progress = TaskProgress::Waiting(cloned)
-                            }
-                            _ => {}
+                    StepOutcome::Error(ref err) => {
+                        state.progress = TaskProgress::Error(err.to_string());
+                        *state.transcript.lock().unwrap() = state.checkout.transcript().cloned();
+
+                        let report = Report::new(
+                            Event::Error,
+                            err.to_string(),
+                            state.uid,
+                            &state.phone,
+                            &state.checkout.ctx,
+                        )
+                        .code(err.code());
+                        for (name, err) in state.notifiers.notify(&report).await {
+                            log::Record::new(
+                                Level::Warn,
+                                Tag::task(state.uid),
+                                format!("Can't notify {} about the error: {}", name, err),
+                            )
+                            .log();
                         }
                     }
-                    LoopAction::Move(ref step, ref msg) => {
-                        state.progress = match step {
-                            BackgroundStep::Start => TaskProgress::Start,
-                            BackgroundStep::Warmup => TaskProgress::WarmingUp,
-                            BackgroundStep::Waiting => TaskProgress::Waiting(msg.clone()),
-                            BackgroundStep::Process { .. } => TaskProgress::Processing,
-                            BackgroundStep::End { .. } => TaskProgress::Completing(msg.clone()),
-                        };
-                        state.step = step.clone();
-                    }
-                    LoopAction::Error(ref msg) => {
-                        state.progress = TaskProgress::Error(String::from(msg));
-                    }
-                    LoopAction::Complete(ref msg) => {
+                    StepOutcome::Complete(ref msg) => {
                         state.progress = TaskProgress::Complete(msg.clone());
                     }
-                    LoopAction::Failed(ref msg) => {
+                    StepOutcome::Failed(ref msg) => {
                         state.progress = TaskProgress::Failed(msg.clone());
                     }
                 }
 
                 match action {
-                    LoopAction::Continue => {}
-                    _ => {
-                        match action {
-                            LoopAction::Break(_) => {}
-                            LoopAction::Error(_)
-                            | LoopAction::Complete(_)
-                            | LoopAction::Failed(_) => state.stopped = true,
-                            _ => state.substep = 0,
-                        }
+                    StepOutcome::Continue => {}
+                    StepOutcome::Error(_) | StepOutcome::Complete(_) | StepOutcome::Failed(_) => {
+                        state.stopped = true;
                         break;
                     }
+                    _ => break,
                 }
             }
 
-            Some(((uid, state.progress.clone()), state))
+            Some((state.progress.clone(), state))
         }))
     }
 }
 
-struct BackgroundState {
-    card: ProductCard,
-    variant: Variant,
-    size: Size,
+/// The end stage is entered without a result
+fn unknown(tier: &str) -> StepOutcome {
+    StepOutcome::Error(Error::new(ErrorKind::Internal(String::from("Unknown"))).tier(tier))
+}
 
+/// Writes the outcome to the order history (End stage)
+struct Record {
+    uid: u64,
     phone: String,
-    webhook: Webhook,
-    flags: (bool, bool, bool, u64),
+}
 
-    client: Client,
-    progress: TaskProgress,
+#[async_trait]
+impl CheckoutStep for Record {
+    fn tier(&self) -> &'static str {
+        "End/H"
+    }
 
-    step: BackgroundStep,
-    substep: u8,
-    start: SystemTime,
-    stopped: bool,
-    link: Arc<()>,
-}
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let (content, kind) = match ctx.end {
+            Some(ref end) => end,
+            None => return unknown(self.tier()),
+        };
+        let unix = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|dur| dur.as_secs())
+                .unwrap_or_default()
+        };
+
+        let entry = Entry {
+            task: self.uid,
+            outcome: Outcome::from(kind),
+            detail: match kind {
+                EndKind::Succeed(_) => ctx.client.site().url(content),
+                _ => content.clone(),
+            },
+            product: ctx
+                .items
+                .iter()
+                .map(|i| i.card.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", "),
+            size: ctx
+                .items
+                .iter()
+                .map(|i| format!("{} ×{}", i.size_name(), i.quantity))
+                .collect::<Vec<String>>()
+                .join(", "),
+            price: ctx.cart.total_price,
+            account: self.phone.clone(),
+            elapsed: ctx
+                .start
+                .elapsed()
+                .map(|dur| dur.as_millis() as u64)
+                .unwrap_or_default(),
+            started: unix(ctx.start),
+            finished: unix(SystemTime::now()),
+        };
+
+        // The order is placed anyway, so the task goes on without the record
+        if let Err(err) = history::append(&entry) {
+            log::Record::new(
+                Level::Error,
+                ctx.client.tag(),
+                format!("Can't write the order history: {}", err),
+            )
+            .log();
+        }
 
-#[derive(Clone)]
-enum BackgroundStep {
-    Start,
-    Warmup,
-    Waiting,
-    Process {
-        cart: Basket,
-    },
-    End {
-        content: String,
-        cart: Basket,
-        kind: EndKind,
-    },
+        StepOutcome::Break(Some(String::from("Notifying")))
+    }
 }
 
-#[derive(Clone)]
-enum EndKind {
-    Succeed(bool),
-    UserAction,
-    Failed,
+/// Sends the result report to the notifiers (End stage). Delivery failures are left to the task
+/// and don't change the outcome
+struct Notify {
+    uid: u64,
+    phone: String,
+    notifiers: Notifiers,
+    delivery: Arc<Mutex<Option<String>>>,
 }
 
-enum LoopAction {
-    Continue,
+#[async_trait]
+impl CheckoutStep for Notify {
+    fn tier(&self) -> &'static str {
+        "End"
+    }
 
-    Break(Option<String>),
-    Move(BackgroundStep, Option<String>),
+    async fn run(&self, ctx: &mut Ctx) -> StepOutcome {
+        let report = match Report::end(self.uid, &self.phone, ctx) {
+            Some(report) => report,
+            None => return unknown(self.tier()),
+        };
+        let failed = self.notifiers.notify(&report).await;
+
+        if !failed.is_empty() {
+            let failed = failed
+                .iter()
+                .map(|(name, err)| format!("{}: {}", name, err))
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            log::Record::new(
+                Level::Warn,
+                ctx.client.tag(),
+                format!("Notification not delivered ({})", failed),
+            )
+            .log();
+            *self.delivery.lock().unwrap() = Some(failed);
+        }
 
-    Error(String),
-    Complete(Option<String>),
-    Failed(Option<String>),
+        match report.event {
+            Event::Success => StepOutcome::Complete(Some(String::from(if report.confirmed {
+                "Success"
+            } else {
+                "Success (Unconfirmed)"
+            }))),
+            Event::UserAction => StepOutcome::Complete(Some(String::from("User Action Required"))),
+            Event::Failure | Event::Error => StepOutcome::Failed(Some(String::from("Bank error"))),
+        }
+    }
 }
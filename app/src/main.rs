@@ -1,9 +1,12 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::{env, process};
+
 use iced::{window, Application, Result, Settings};
 
 use layout::Layout;
 
+mod headless;
 mod icons;
 mod layout;
 mod logic;
@@ -22,28 +25,26 @@ static ACCOUNTS_FILE: &str = "./accounts.json";
 static PROXY_FILE: &str = "./proxy.json";
 static SETTINGS_FILE: &str = "./settings.json";
 static LICENSE_FILE: &str = "./license.jwt";
+static TASKS_FILE: &str = "./tasks.json";
+static EMBEDS_FILE: &str = "./embeds.toml";
+static HISTORY_FILE: &str = "./history.jsonl";
+static HISTORY_CSV_FILE: &str = "./history.csv";
+static LOG_FILE: &str = "./sdp.log";
+static REPORTS_DIR: &str = "./reports";
 
-#[cfg(target_os = "linux")]
-static H_USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:91.0) Gecko/20100101 Firefox/91.0";
-#[cfg(target_os = "windows")]
-static H_USER_AGENT: &str =
-    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:91.0) Gecko/20100101 Firefox/91.0";
+fn main() -> Result {
+    logic::logs::init();
 
-static H_HOST: &str = "www.wildberries.ru";
-static H_ACCEPT: &str = "*/*";
-static H_ACCEPT_LANGUAGE: &str = "ru-RU,ru;q=0.5";
-static H_ACCEPT_ENCODING: &str = "gzip, deflate, br";
-static H_X_REQUESTED_WITH: &str = "XMLHttpRequest";
-static H_X_SPA_VERSION: &str = "8.0.4";
-static H_SEC_FETCH_DEST: &str = "empty";
-static H_SEC_FETCH_MODE: &str = "cors";
-static H_SEC_FETCH_SITE: &str = "same-origin";
-static H_PRAGMA: &str = "no-cache";
-static H_CACHE_CONTROL: &str = "no-cache";
-static H_ORIGIN: &str = "https://www.wildberries.ru";
-static H_TE: &str = "trailers";
+    if let Some(args) = headless::Args::parse(env::args()) {
+        process::exit(match args {
+            Ok(args) => headless::run(args),
+            Err(msg) => {
+                eprintln!("{}", msg);
+                2
+            }
+        });
+    }
 
-fn main() -> Result {
     #[cfg(debug_assertions)]
     let icon = include_bytes!("../assets/images/logo.rev").to_vec();
     #[cfg(not(debug_assertions))]
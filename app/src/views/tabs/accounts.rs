@@ -1,17 +1,19 @@
+use api::{
+    client::WildberriesClient,
+    error::ErrorKind,
+    log::{Level, Record, Tag},
+    models::{ResponseValue, User},
+    site::Site,
+};
 use iced::{
     button, scrollable, text_input, Align, Button, Checkbox, Column, Command, Container, Element,
     HorizontalAlignment, Length, Row, Scrollable, Space, Text, TextInput,
 };
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
 
 use crate::{
     icons::{icon, Icon},
     layout::Message,
-    logic::{
-        misc::{client, request, RequestMethod},
-        models::{ResponseResult, ResponseValue, User},
-    },
     themes::Theme,
 };
 
@@ -42,33 +44,28 @@ impl Account {
         }
     }
 
-    pub async fn info(token: &String) -> Result<User, AccountError> {
-        match request(
-            &mut client(
-                None,
-                Some(&[(
-                    String::from("WILDAUTHNEW_V3"),
-                    token.clone(),
-                    String::from("wildberries.ru"),
-                )]),
-            ),
-            &String::from("https://wildberries.ru/lk/personalcabinet/data"),
-            RequestMethod::GET,
-            "https://wildberries.ru/lk/",
-            0,
-        )
-        .await
+    pub async fn info(site: Site, token: &str) -> Result<User, AccountError> {
+        match WildberriesClient::new(site, None, Some(token))
+            .personal_cabinet_data(0)
+            .await
         {
-            Ok(resp) => {
-                let result = from_str::<ResponseResult>(&resp.body).unwrap();
-                if let ResponseValue::Value(value) = result.value {
-                    Ok(value.user.unwrap())
-                } else {
-                    Err(AccountError::InvalidToken)
-                }
+            Ok(result) => match result.value {
+                ResponseValue::Value(value) => match value.user {
+                    Some(user) => Ok(user),
+                    None => Err(AccountError::InvalidToken),
+                },
+                _ => Err(AccountError::InvalidToken),
+            },
+            Err(err) if matches!(err.kind, ErrorKind::Response { .. }) => {
+                Err(AccountError::InvalidToken)
             }
             Err(err) => {
-                println!("Err: {:?}", err);
+                Record::new(
+                    Level::Warn,
+                    Tag::default(),
+                    format!("Account check failed: {:?}", err),
+                )
+                .log();
                 Err(AccountError::Unknown)
             }
         }
@@ -144,7 +141,12 @@ pub enum AccountsTab {
 }
 
 impl AccountsTab {
-    pub fn update(&mut self, msg: AccountsMsg, accounts: &mut Vec<Account>) -> Command<Message> {
+    pub fn update(
+        &mut self,
+        msg: AccountsMsg,
+        accounts: &mut Vec<Account>,
+        site: Site,
+    ) -> Command<Message> {
         match msg {
             AccountsMsg::List => *self = AccountsTab::default(),
             AccountsMsg::Add => {
@@ -176,7 +178,7 @@ impl AccountsTab {
                             *checking = true;
                             let token = token.clone();
                             return Command::perform(
-                                async move { Account::info(&token).await },
+                                async move { Account::info(site, &token).await },
                                 |result| AccountsMsg::CheckEnd(result).into(),
                             );
                         } else {
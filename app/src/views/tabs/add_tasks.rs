@@ -1,19 +1,25 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    time::SystemTime,
+};
 
+use api::{
+    checkout::{Item, PriceGuard},
+    client::WildberriesClient,
+    error::ErrorKind,
+    models::{ProductCard, ResponseValue, SizeTag, Variant},
+    site::Site,
+};
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
 use iced::{
-    button, pick_list, text_input, Button, Column, Command, Container, Element,
+    button, pick_list, text_input, Button, Checkbox, Column, Command, Container, Element,
     HorizontalAlignment, Length, PickList, Row, Space, Text, TextInput, VerticalAlignment,
 };
 use serde::Deserialize;
-use serde_json::from_str;
 
 use crate::{
     icons::{icon, Icon},
     layout::Message,
-    logic::{
-        misc::{client, request, RequestMethod, ResponseStatus},
-        models::{ProductCard, ResponseResult, ResponseValue, SizeTag, Variant, Webhook},
-    },
     themes::Theme,
 };
 
@@ -46,6 +52,40 @@ impl Display for SizeOld {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Start time
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Parses the drop time. A time without a date is the next one to come, an empty input is none
+fn parse_start(input: &str) -> Option<Option<SystemTime>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Some(None);
+    }
+
+    let now = Local::now().naive_local();
+    let start = ["%d.%m.%Y %H:%M:%S", "%d.%m.%Y %H:%M"]
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(input, f).ok())
+        .or_else(|| {
+            let time = ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|f| NaiveTime::parse_from_str(input, f).ok())?;
+            let today = now.date().and_time(time);
+
+            Some(if today > now {
+                today
+            } else {
+                today + Duration::days(1)
+            })
+        })?;
+
+    Local
+        .from_local_datetime(&start)
+        .earliest()
+        .map(|start| Some(SystemTime::from(start)))
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // CreateTasks Tab
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -53,9 +93,17 @@ impl Display for SizeOld {
 #[derive(Clone, Debug)]
 pub enum AddTasksMsg {
     CodChange(String),
+    /// Adds the size to the priority list or removes it from there
     SizeSelected(SizeTag),
+    AnySize(bool),
+    QuantityChange(String),
+    MaxPriceChange(String),
+    MinDiscountChange(String),
+    StartChange(String),
     Check,
     Checked(Option<(ProductCard, Variant)>),
+    /// Keeps the selected item and starts choosing the next one of the order
+    Bundle,
     Create,
     Done(String),
     Reset,
@@ -74,10 +122,24 @@ pub struct AddTasksTab {
 
     cod: String,
     product: Option<(ProductCard, Variant)>,
-    size: Option<SizeTag>,
+    /// Acceptable sizes in priority order
+    sizes: Vec<SizeTag>,
+    any_size: bool,
+    quantity: String,
+    /// Items already chosen for the order
+    items: Vec<Item>,
+    max_price: String,
+    min_discount: String,
+    /// Drop time, the tasks start right away if empty
+    start: String,
 
     cod_input: text_input::State,
     size_pick: pick_list::State<SizeTag>,
+    quantity_input: text_input::State,
+    max_price_input: text_input::State,
+    min_discount_input: text_input::State,
+    start_input: text_input::State,
+    bundle_btn: button::State,
     step_btn: button::State,
     reset_btn: button::State,
 }
@@ -86,92 +148,131 @@ impl AddTasksTab {
     fn reset(&mut self) {
         self.error = String::new();
         self.product = None;
-        self.size = None;
+        self.sizes.clear();
+        self.any_size = false;
+        self.quantity = String::new();
     }
 
-    pub fn update(&mut self, msg: AddTasksMsg) -> Command<Message> {
+    /// Item of the selected product and sizes
+    fn item(&self) -> Option<Item> {
+        let (card, variant) = self.product.clone()?;
+        let quantity = self.quantity.parse::<u64>().unwrap_or(1).max(1);
+
+        if variant.sizes.len() == 1 || self.any_size {
+            let size = variant.sizes.values().next()?.clone();
+            return Some(Item::new(card, variant, size, quantity).sizes(Vec::new()));
+        }
+
+        let size = variant
+            .sizes
+            .get(&self.sizes.first()?.id.to_string())?
+            .clone();
+        let sizes = self.sizes.iter().map(|s| s.id).collect();
+
+        Some(Item::new(card, variant, size, quantity).sizes(sizes))
+    }
+
+    pub fn update(&mut self, msg: AddTasksMsg, site: Site) -> Command<Message> {
         match msg {
-            AddTasksMsg::CodChange(cod) if cod.parse::<u128>().is_ok() || cod.is_empty() => {
-                if self.size.is_some() {
-                    self.size = None
-                }
+            AddTasksMsg::CodChange(cod) if cod.parse::<u64>().is_ok() || cod.is_empty() => {
+                self.sizes.clear();
                 self.cod = cod
             }
             AddTasksMsg::SizeSelected(size) => {
-                if size.quantity != 0 {
-                    self.size = Some(size)
+                // Sold out sizes are kept too, the task can wait for their restock
+                if self.sizes.contains(&size) {
+                    self.sizes.retain(|s| s != &size)
                 } else {
-                    self.error = String::from("This size cannot be selected (Sold Out)")
+                    self.sizes.push(size)
                 }
             }
+            AddTasksMsg::AnySize(any) => self.any_size = any,
+            AddTasksMsg::QuantityChange(quantity)
+                if quantity.parse::<u64>().is_ok() || quantity.is_empty() =>
+            {
+                self.quantity = quantity
+            }
+            AddTasksMsg::Bundle => {
+                if let Some(item) = self.item() {
+                    self.items.push(item);
+                }
+
+                self.reset();
+                self.cod = String::new();
+            }
+            AddTasksMsg::MaxPriceChange(price)
+                if price.parse::<u64>().is_ok() || price.is_empty() =>
+            {
+                self.max_price = price
+            }
+            AddTasksMsg::MinDiscountChange(discount)
+                if discount
+                    .parse::<u64>()
+                    .map_or(discount.is_empty(), |d| d <= 100) =>
+            {
+                self.min_discount = discount
+            }
+            AddTasksMsg::StartChange(start) => self.start = start,
             AddTasksMsg::Create => {
-                let (card, variant) = self.product.clone().unwrap();
-                let size = if variant.sizes.len() > 1 {
-                    variant.sizes[&self.size.clone().unwrap().id.to_string()].clone()
-                } else {
-                    variant.sizes.values().next().cloned().unwrap()
+                let drop = match parse_start(&self.start) {
+                    Some(drop) => drop,
+                    None => {
+                        self.error =
+                            String::from("Start time must be HH:MM[:SS] or DD.MM.YYYY HH:MM[:SS]");
+                        return Command::none();
+                    }
+                };
+                let mut items = std::mem::take(&mut self.items);
+                items.extend(self.item());
+                let guard = PriceGuard {
+                    max_price: self.max_price.parse::<u64>().ok(),
+                    min_discount: self.min_discount.parse::<u64>().ok(),
                 };
 
                 self.reset();
+                self.max_price = String::new();
+                self.min_discount = String::new();
+                self.start = String::new();
 
                 return Command::perform(
-                    async move { (card, variant, size) },
-                    |(card, variant, size)| Message::AddTasks {
-                        card,
-                        variant,
-                        size,
-                    },
+                    async move { (items, guard, drop) },
+                    |(items, guard, drop)| Message::AddTasks(items, guard, drop),
                 );
             }
             AddTasksMsg::Check => {
                 self.processing = true;
                 self.error = String::new();
-                let cod = self.cod.clone();
+                let cod = self.cod.parse::<u64>().unwrap_or_default();
 
                 return Command::perform(
                     async move {
-                        match request(
-                            &mut client(None, None),
-                            &format!(
-                                "https://www.wildberries.ru/{}/product/data?targetUrl=XS",
-                                cod
-                            ),
-                            RequestMethod::GET,
-                            &format!(
-                                "https://www.wildberries.ru/catalog/{}/detail.aspx?targetUrl=XS",
-                                cod
-                            ),
-                            0,
-                        )
-                        .await
+                        match WildberriesClient::new(site, None, None)
+                            .product_data(cod, 0)
+                            .await
                         {
-                            Ok(resp) => match from_str::<ResponseResult>(&resp.body) {
-                                Ok(result) => {
-                                    if let ResponseValue::Value(value) = result.value {
-                                        let variant = match value.data.variant {
-                                            Some(variant) => variant,
-                                            None => return ("Can't parse product variant", None),
-                                        };
-
-                                        match value.data.product_card {
-                                            Some(card) => ("", Some((card, variant))),
-                                            None => ("Can't parse product info", None),
-                                        }
-                                    } else {
-                                        ("Product scheme error", None)
+                            Ok(result) => {
+                                if let ResponseValue::Value(value) = result.value {
+                                    let variant = match value.data.variant {
+                                        Some(variant) => variant,
+                                        None => return ("Can't parse product variant", None),
+                                    };
+
+                                    match value.data.product_card {
+                                        Some(card) => ("", Some((card, variant))),
+                                        None => ("Can't parse product info", None),
                                     }
+                                } else {
+                                    ("Product scheme error", None)
                                 }
-                                Err(_) => ("Product not found", None),
-                            },
-                            Err(err) => {
-                                return (
-                                    match err {
-                                        ResponseStatus::Timeout => "Timeout",
-                                        ResponseStatus::ConnectionError => "Connection Error",
-                                    },
-                                    None,
-                                )
                             }
+                            Err(err) => match err.kind {
+                                ErrorKind::NotFound | ErrorKind::Response { .. } => {
+                                    ("Product not found", None)
+                                }
+                                ErrorKind::Timeout => ("Timeout", None),
+                                ErrorKind::Http(_) => ("Server error", None),
+                                _ => ("Connection Error", None),
+                            },
                         }
                     },
                     move |(err, result)| {
@@ -192,14 +293,22 @@ impl AddTasksTab {
                 self.error = msg;
                 self.processing = false;
             }
-            AddTasksMsg::Reset => self.reset(),
+            AddTasksMsg::Reset => {
+                self.reset();
+                self.items.clear();
+                self.max_price = String::new();
+                self.min_discount = String::new();
+                self.start = String::new();
+            }
             _ => (),
         }
 
         Command::none()
     }
 
-    pub fn view(&mut self, theme: &Theme, webhook: &Webhook) -> Element<Message> {
+    pub fn view(&mut self, theme: &Theme, notified: bool) -> Element<'_, Message> {
+        let has_item = self.item().is_some();
+
         let mut step_btn = Button::new(
             &mut self.step_btn,
             Text::new(if self.product.is_none() {
@@ -214,18 +323,24 @@ impl AddTasksTab {
         .width(Length::Units(128))
         .style(theme.primary_btn());
 
-        if !self.cod.is_empty() && !self.processing {
+        let mut bundle_btn = Button::new(
+            &mut self.bundle_btn,
+            Text::new("Add to order")
+                .width(Length::Fill)
+                .horizontal_alignment(HorizontalAlignment::Center),
+        )
+        .padding(8)
+        .width(Length::Units(128))
+        .style(theme.primary_btn());
+
+        if !self.processing {
             if self.product.is_none() {
-                step_btn = step_btn.on_press(AddTasksMsg::Check.into());
-            } else {
-                match &self.product {
-                    Some((_, variant)) => {
-                        if variant.sizes.len() == 1 || self.size.is_some() {
-                            step_btn = step_btn.on_press(AddTasksMsg::Create.into());
-                        }
-                    }
-                    None => {}
+                if !self.cod.is_empty() {
+                    step_btn = step_btn.on_press(AddTasksMsg::Check.into());
                 }
+            } else if has_item {
+                step_btn = step_btn.on_press(AddTasksMsg::Create.into());
+                bundle_btn = bundle_btn.on_press(AddTasksMsg::Bundle.into());
             }
         }
 
@@ -252,7 +367,29 @@ impl AddTasksTab {
             )
             .push(Space::with_height(Length::Units(
                 if self.error.is_empty() { 0 } else { 8 },
-            )))
+            )));
+
+        if !self.items.is_empty() {
+            inner = inner
+                .push(Text::new("Order"))
+                .push(Space::with_height(Length::Units(8)));
+
+            for item in &self.items {
+                inner = inner.push(
+                    Text::new(format!(
+                        "{} ({}) ×{}",
+                        item.card.name,
+                        item.sizes_name(),
+                        item.quantity
+                    ))
+                    .color(theme.color_text_muted()),
+                );
+            }
+
+            inner = inner.push(Space::with_height(Length::Units(16)));
+        }
+
+        inner = inner
             .push(Text::new(if self.product.is_none() {
                 "PID"
             } else {
@@ -275,22 +412,109 @@ impl AddTasksTab {
                 .style(theme.text_input()),
             );
 
+        if self.product.is_some() {
+            inner = inner
+                .push(Space::with_height(Length::Units(16)))
+                .push(Text::new("Quantity"))
+                .push(Space::with_height(Length::Units(8)))
+                .push(
+                    TextInput::new(&mut self.quantity_input, "1", &self.quantity, |quantity| {
+                        AddTasksMsg::QuantityChange(quantity).into()
+                    })
+                    .padding(8)
+                    .style(theme.text_input()),
+                )
+                .push(Space::with_height(Length::Units(16)))
+                .push(
+                    Row::new()
+                        .push(Text::new("Max price (RUB)").width(Length::Fill))
+                        .push(Space::with_width(Length::Units(16)))
+                        .push(Text::new("Min discount (%)").width(Length::Fill)),
+                )
+                .push(Space::with_height(Length::Units(8)))
+                .push(
+                    Row::new()
+                        .push(
+                            TextInput::new(
+                                &mut self.max_price_input,
+                                "Any",
+                                &self.max_price,
+                                |price| AddTasksMsg::MaxPriceChange(price).into(),
+                            )
+                            .padding(8)
+                            .style(theme.text_input()),
+                        )
+                        .push(Space::with_width(Length::Units(16)))
+                        .push(
+                            TextInput::new(
+                                &mut self.min_discount_input,
+                                "Any",
+                                &self.min_discount,
+                                |discount| AddTasksMsg::MinDiscountChange(discount).into(),
+                            )
+                            .padding(8)
+                            .style(theme.text_input()),
+                        ),
+                )
+                .push(Space::with_height(Length::Units(16)))
+                .push(Text::new("Start time"))
+                .push(Space::with_height(Length::Units(8)))
+                .push(
+                    TextInput::new(&mut self.start_input, "Right away", &self.start, |start| {
+                        AddTasksMsg::StartChange(start).into()
+                    })
+                    .padding(8)
+                    .style(theme.text_input()),
+                );
+        }
+
         match &self.product {
             Some((_, variant)) => {
                 if variant.sizes.len() > 1 {
                     inner = inner
                         .push(Space::with_height(Length::Units(16)))
-                        .push(Text::new("Size"))
+                        .push(Text::new("Sizes"))
                         .push(Space::with_height(Length::Units(8)))
-                        .push(
-                            PickList::new(
-                                &mut self.size_pick,
-                                variant.sizes_tags(),
-                                self.size.clone(),
-                                |variant| AddTasksMsg::SizeSelected(variant).into(),
+                        .push(Checkbox::new(self.any_size, "Any size", |any| {
+                            AddTasksMsg::AnySize(any).into()
+                        }));
+
+                    if !self.any_size {
+                        inner = inner
+                            .push(Space::with_height(Length::Units(8)))
+                            .push(
+                                PickList::new(
+                                    &mut self.size_pick,
+                                    variant.sizes_tags(),
+                                    self.sizes.last().cloned(),
+                                    |variant| AddTasksMsg::SizeSelected(variant).into(),
+                                )
+                                .width(Length::Fill),
                             )
-                            .width(Length::Fill),
-                        )
+                            .push(Space::with_height(Length::Units(8)))
+                            .push(
+                                Text::new(&if self.sizes.is_empty() {
+                                    String::from("Pick sizes in priority order")
+                                } else {
+                                    self.sizes
+                                        .iter()
+                                        .map(|s| s.name.as_str())
+                                        .collect::<Vec<&str>>()
+                                        .join(" / ")
+                                })
+                                .color(theme.color_text_muted()),
+                            );
+
+                        if self.sizes.iter().any(|s| s.quantity == 0) {
+                            inner = inner.push(Space::with_height(Length::Units(8))).push(
+                                Text::new(
+                                    "Sold out sizes are waited for until restocked \
+                                     (Monitor in the settings)",
+                                )
+                                .color(theme.color_text_muted()),
+                            );
+                        }
+                    }
                 }
             }
             None => (),
@@ -298,7 +522,7 @@ impl AddTasksTab {
 
         let mut content = Column::new();
 
-        if webhook.id == 0 || webhook.token.is_empty() {
+        if !notified {
             content = content
                 .push(
                     Container::new(
@@ -306,7 +530,7 @@ impl AddTasksTab {
                             .push(icon(Icon::Alert).size(48).width(Length::Units(48)))
                             .push(Space::with_width(Length::Units(32)))
                             .push(
-                                Text::new("Discord webhook is not configured!")
+                                Text::new("Notifications are not configured!")
                                     .size(32)
                                     .height(Length::Units(48))
                                     .vertical_alignment(VerticalAlignment::Center),
@@ -324,7 +548,16 @@ impl AddTasksTab {
                 Container::new(
                     inner
                         .push(Space::with_height(Length::Units(16)))
-                        .push(Container::new(step_btn).width(Length::Fill).center_x())
+                        .push(
+                            Container::new(
+                                Row::new()
+                                    .push(bundle_btn)
+                                    .push(Space::with_width(Length::Units(16)))
+                                    .push(step_btn),
+                            )
+                            .width(Length::Fill)
+                            .center_x(),
+                        )
                         .push(Space::with_height(Length::Units(24)))
                         .push(
                             Container::new(
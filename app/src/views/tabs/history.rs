@@ -0,0 +1,250 @@
+use std::{
+    fmt::{Display, Formatter, Result},
+    path::Path,
+};
+
+use iced::{
+    button, pick_list, scrollable, text_input, Align, Button, Color, Column, Container, Element,
+    HorizontalAlignment, Length, PickList, Row, Scrollable, Text, TextInput,
+};
+
+use crate::{
+    layout::Message,
+    logic::history::{self, Entry, Outcome},
+    themes::Theme,
+    HISTORY_CSV_FILE,
+};
+
+use super::{tab, TabMsg};
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Filter
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Eq, PartialEq, Copy, Clone, Default, Debug)]
+pub enum HistoryFilter {
+    #[default]
+    All,
+    Succeed,
+    UserAction,
+    Failed,
+}
+
+impl HistoryFilter {
+    pub const ALL: [HistoryFilter; 4] = [
+        HistoryFilter::All,
+        HistoryFilter::Succeed,
+        HistoryFilter::UserAction,
+        HistoryFilter::Failed,
+    ];
+
+    fn matches(&self, outcome: Outcome) -> bool {
+        match self {
+            HistoryFilter::All => true,
+            HistoryFilter::Succeed => {
+                matches!(outcome, Outcome::Confirmed | Outcome::Unconfirmed)
+            }
+            HistoryFilter::UserAction => outcome == Outcome::UserAction,
+            HistoryFilter::Failed => outcome == Outcome::Failed,
+        }
+    }
+}
+
+impl Display for HistoryFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                HistoryFilter::All => "All",
+                HistoryFilter::Succeed => "Success",
+                HistoryFilter::UserAction => "User Action",
+                HistoryFilter::Failed => "Failed",
+            }
+        )
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// History Tab
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub enum HistoryMsg {
+    Search(String),
+    Filter(HistoryFilter),
+    Export,
+}
+
+impl From<HistoryMsg> for Message {
+    fn from(msg: HistoryMsg) -> Message {
+        Message::TabMsg(TabMsg::HistoryMsg(msg))
+    }
+}
+
+#[derive(Default)]
+pub struct HistoryTab {
+    entries: Vec<Entry>,
+    search: String,
+    filter: HistoryFilter,
+    status: String,
+
+    search_input: text_input::State,
+    filter_pick: pick_list::State<HistoryFilter>,
+    export_btn: button::State,
+    table_scroll: scrollable::State,
+}
+
+impl HistoryTab {
+    /// Reads the journal again, the newest entries first
+    pub fn reload(&mut self) {
+        self.entries = history::load();
+        self.entries.reverse();
+        self.status = String::new();
+    }
+
+    fn filtered(&self) -> Vec<&Entry> {
+        let search = self.search.to_lowercase();
+
+        self.entries
+            .iter()
+            .filter(|e| self.filter.matches(e.outcome))
+            .filter(|e| {
+                search.is_empty()
+                    || e.product.to_lowercase().contains(&search)
+                    || e.size.to_lowercase().contains(&search)
+                    || e.account.contains(&search)
+            })
+            .collect()
+    }
+
+    pub fn update(&mut self, msg: HistoryMsg) {
+        match msg {
+            HistoryMsg::Search(search) => self.search = search,
+            HistoryMsg::Filter(filter) => self.filter = filter,
+            HistoryMsg::Export => {
+                let entries = self.filtered();
+
+                self.status = match history::export_csv(Path::new(HISTORY_CSV_FILE), &entries) {
+                    Ok(()) => format!("Exported {} entries to {}", entries.len(), HISTORY_CSV_FILE),
+                    Err(err) => format!("Export failed: {}", err),
+                };
+            }
+        }
+    }
+
+    pub fn view(&mut self, theme: &Theme) -> Element<'_, Message> {
+        let entries = self.filtered().into_iter().cloned().collect::<Vec<Entry>>();
+
+        let controls = Row::new()
+            .push(
+                TextInput::new(
+                    &mut self.search_input,
+                    "Search product, size or account",
+                    &self.search,
+                    |search| HistoryMsg::Search(search).into(),
+                )
+                .padding(8)
+                .width(Length::FillPortion(3))
+                .style(theme.text_input()),
+            )
+            .push(
+                PickList::new(
+                    &mut self.filter_pick,
+                    &HistoryFilter::ALL[..],
+                    Some(self.filter),
+                    |filter| HistoryMsg::Filter(filter).into(),
+                )
+                .width(Length::FillPortion(1)),
+            )
+            .push(
+                Button::new(
+                    &mut self.export_btn,
+                    Text::new("Export CSV")
+                        .width(Length::Fill)
+                        .horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .on_press(HistoryMsg::Export.into())
+                .width(Length::Units(128))
+                .padding(8)
+                .style(theme.primary_btn()),
+            )
+            .align_items(Align::Center)
+            .spacing(8);
+
+        let mut content = tab(&String::from("History"))
+            .push(controls)
+            .push(Text::new(&self.status).color(theme.color_text_muted()));
+
+        content = content.push::<Element<Message>>(if entries.is_empty() {
+            Text::new("No orders yet").into()
+        } else {
+            entries
+                .iter()
+                .fold(
+                    Scrollable::new(&mut self.table_scroll)
+                        .width(Length::Fill)
+                        .spacing(8),
+                    |table, entry| table.push(row(theme, entry)),
+                )
+                .into()
+        });
+
+        content.into()
+    }
+}
+
+fn row<'a>(theme: &Theme, entry: &Entry) -> Element<'a, Message> {
+    let field = |name: &str, value: &str, fill: u16| {
+        Column::new()
+            .push(Text::new(name).color(theme.color_text_muted()).size(15))
+            .push(Text::new(value).size(19))
+            .width(Length::FillPortion(fill))
+    };
+
+    Container::new(
+        Column::new()
+            .push(
+                Row::new()
+                    .push(Text::new(format!("#{}", entry.task)).width(Length::Units(24)))
+                    .push(Text::new(&entry.product).width(Length::FillPortion(2)))
+                    .push(
+                        Text::new(entry.outcome.to_string())
+                            .width(Length::FillPortion(1))
+                            .color(color(entry.outcome)),
+                    )
+                    .spacing(8),
+            )
+            .push(
+                Row::new()
+                    .push(field("Size", &entry.size, 2))
+                    .push(field("Price", &format!("{} RUB", entry.price), 2))
+                    .push(field("Account", &entry.account, 2))
+                    .push(field(
+                        "Elapsed",
+                        &format!("{:.3} sec", entry.elapsed as f32 / 1000.0),
+                        2,
+                    ))
+                    .push(field("Finished", &history::time(entry.finished), 3))
+                    .spacing(8),
+            )
+            .push(
+                Text::new(&entry.detail)
+                    .color(theme.color_text_muted())
+                    .size(15),
+            )
+            .padding(8)
+            .spacing(8),
+    )
+    .style(theme.card())
+    .into()
+}
+
+fn color(outcome: Outcome) -> Color {
+    match outcome {
+        Outcome::Confirmed => Color::from_rgb(0.392, 0.867, 0.09),
+        Outcome::Unconfirmed => Color::from_rgb(0.0, 0.784, 0.325),
+        Outcome::UserAction => Color::from_rgb(1.0, 0.671, 0.0),
+        Outcome::Failed => Color::from_rgb(0.835, 0.0, 0.0),
+    }
+}
@@ -7,18 +7,20 @@ use iced::{
 
 use accounts::{Account, AccountsMsg};
 use add_tasks::AddTasksMsg;
+use history::HistoryMsg;
 use proxy::Proxy;
 use settings::SettingsMsg;
 
 use crate::{
     icons::{icon, Icon},
     layout::Message,
-    logic::{activation::Activation, models::Settings, task::Task},
+    logic::{activation::Activation, models::Settings, notify::Notifiers, task::Task},
     themes::Theme,
 };
 
 pub mod accounts;
 pub mod add_tasks;
+pub mod history;
 pub mod home;
 pub mod proxy;
 pub mod settings;
@@ -93,12 +95,16 @@ impl TabsViewState {
                                     .into(),
                                 Tab::Tasks(ref mut state) => state.view(&settings.theme, tasks),
                                 Tab::AddTasks(ref mut state) => content_scroll
-                                    .push(state.view(&settings.theme, &settings.webhook))
+                                    .push(state.view(
+                                        &settings.theme,
+                                        !Notifiers::new(settings).is_empty(),
+                                    ))
                                     .into(),
                                 Tab::Accounts(ref mut state) => {
                                     state.view(&settings.theme, accounts)
                                 }
                                 Tab::Proxy(ref mut state) => state.view(&settings.theme, proxies),
+                                Tab::History(ref mut state) => state.view(&settings.theme),
                             },
                             None => Text::new(format!("Unknown tab: {}", tab)).into(),
                         })
@@ -127,6 +133,7 @@ pub enum Tab {
     AddTasks(self::add_tasks::AddTasksTab),
     Accounts(self::accounts::AccountsTab),
     Proxy(self::proxy::ProxyTab),
+    History(self::history::HistoryTab),
 }
 
 impl Tab {
@@ -138,6 +145,7 @@ impl Tab {
             Tab::AddTasks(..) => Icon::Add,
             Tab::Accounts(..) => Icon::Account,
             Tab::Proxy(..) => Icon::Server,
+            Tab::History(..) => Icon::Accept,
         }
     }
 }
@@ -153,6 +161,7 @@ pub enum TabMsg {
     SettingsMsg(SettingsMsg),
     AddTasksMsg(AddTasksMsg),
     AccountsMsg(AccountsMsg),
+    HistoryMsg(HistoryMsg),
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -1,9 +1,18 @@
+use api::site::{Site, DEFAULT_SITE_URL};
 use iced::{
-    button, pick_list, slider, text_input, Align, Button, Checkbox, Container, Element, Length,
-    PickList, Row, Text, TextInput,
+    button, pick_list, slider, text_input, Align, Button, Checkbox, Color, Column, Command,
+    Container, Element, HorizontalAlignment, Length, PickList, Row, Text, TextInput,
 };
 
-use crate::{layout::Message, logic::models::Settings, themes::Theme, VERSION};
+use crate::{
+    layout::Message,
+    logic::{
+        models::{ControlApi, Settings, Sink, Webhook},
+        notify::{Discord, Event, Report},
+    },
+    themes::Theme,
+    VERSION,
+};
 
 use super::{proxy::ProxyMode, section, tab, TabMsg};
 
@@ -11,8 +20,24 @@ use super::{proxy::ProxyMode, section, tab, TabMsg};
 pub enum SettingsMsg {
     IdChanged(u64),
     TokenChanged(String),
+    /// Full webhook URL pasted into the ID or token input
+    WebhookPasted(Webhook),
+    TestWebhook,
+    WebhookTested(Result<String, String>),
+    TelegramToken(String),
+    TelegramChat(String),
+    JsonUrl(String),
+    SlackUrl(String),
+    Desktop(bool),
+    Route(Sink, Event, bool),
     ScaleChange(f64),
     ScaleApply,
+    SiteChange(String),
+    SiteApply,
+    ControlEnabled(bool),
+    ControlPort(u16),
+    ControlToken(String),
+    ControlGenerate,
     None,
 }
 
@@ -26,8 +51,24 @@ impl Into<Message> for SettingsMsg {
 pub struct SettingsTab {
     pub id_input: text_input::State,
     pub token_input: text_input::State,
+    pub test_btn: button::State,
+    /// Sending the sample embed or the result of the last one
+    pub testing: bool,
+    pub test_result: Option<Result<String, String>>,
+
+    pub telegram_token_input: text_input::State,
+    pub telegram_chat_input: text_input::State,
+    pub json_url_input: text_input::State,
+    pub slack_url_input: text_input::State,
 
     pub proxy_mode: pick_list::State<ProxyMode>,
+    pub site_input: text_input::State,
+    pub site_apply: button::State,
+    pub site: Option<String>,
+
+    pub control_port_input: text_input::State,
+    pub control_token_input: text_input::State,
+    pub control_generate_btn: button::State,
 
     pub theme_pick: pick_list::State<Theme>,
     pub scale_slider: slider::State,
@@ -35,29 +76,81 @@ pub struct SettingsTab {
     pub scale: f64,
 
     pub monitor_freq_input: text_input::State,
+    pub retry_attempts_input: text_input::State,
+    pub retry_backoff_input: text_input::State,
+    pub retry_jitter_input: text_input::State,
+    pub warmup_lead_input: text_input::State,
 
     pub reset_btn: button::State,
     pub logout_btn: button::State,
 }
 
 impl SettingsTab {
-    pub fn update(&mut self, msg: SettingsMsg, settings: &mut Settings) {
+    pub fn update(&mut self, msg: SettingsMsg, settings: &mut Settings) -> Command<Message> {
         match msg {
-            SettingsMsg::IdChanged(id) => settings.webhook.id = id,
+            SettingsMsg::IdChanged(id) => {
+                settings.webhook.id = id;
+                self.test_result = None;
+            }
             SettingsMsg::TokenChanged(token) => {
                 settings.webhook.token = if token.len() > 68 {
                     token[0..=67].to_string()
                 } else {
                     token
-                }
+                };
+                self.test_result = None;
+            }
+            SettingsMsg::WebhookPasted(webhook) => {
+                settings.webhook = webhook;
+                self.test_result = None;
+            }
+            SettingsMsg::TestWebhook => {
+                let discord = Discord(settings.webhook.clone(), settings.embeds.clone());
+                let report = Report::sample(settings.site().url("/lk/myorders/delivery"));
+
+                self.testing = true;
+                return Command::perform(async move { discord.test(&report).await }, |res| {
+                    SettingsMsg::WebhookTested(res).into()
+                });
+            }
+            SettingsMsg::WebhookTested(res) => {
+                self.testing = false;
+                self.test_result = Some(res);
+            }
+            SettingsMsg::TelegramToken(token) => settings.notifications.telegram.token = token,
+            SettingsMsg::TelegramChat(chat) => settings.notifications.telegram.chat = chat,
+            SettingsMsg::JsonUrl(url) => settings.notifications.json.url = url,
+            SettingsMsg::SlackUrl(url) => settings.notifications.slack.url = url,
+            SettingsMsg::Desktop(enabled) => settings.notifications.desktop.enabled = enabled,
+            SettingsMsg::Route(sink, event, value) => {
+                settings.notifications.route_mut(sink).set(event, value)
             }
             SettingsMsg::ScaleChange(scale) => self.scale = scale,
             SettingsMsg::ScaleApply => settings.scale = self.scale,
+            SettingsMsg::SiteChange(site) => self.site = Some(site),
+            SettingsMsg::SiteApply => {
+                if let Some(site) = self.site.as_ref().and_then(|url| Site::new(url)) {
+                    settings.site = site.to_string();
+                    self.site = Some(settings.site.clone());
+                }
+            }
+            SettingsMsg::ControlEnabled(enabled) => {
+                if enabled && settings.control.token.is_empty() {
+                    settings.control.token = ControlApi::generate_token();
+                }
+
+                settings.control.enabled = enabled;
+            }
+            SettingsMsg::ControlPort(port) => settings.control.port = port,
+            SettingsMsg::ControlToken(token) => settings.control.token = token,
+            SettingsMsg::ControlGenerate => settings.control.token = ControlApi::generate_token(),
             SettingsMsg::None => (),
         }
+
+        Command::none()
     }
 
-    pub fn view(&mut self, settings: &Settings, key: &String) -> Element<Message> {
+    pub fn view(&mut self, settings: &Settings, key: &str) -> Element<'_, Message> {
         if self.scale == 0.0 {
             self.scale = settings.scale;
         }
@@ -68,6 +161,78 @@ impl SettingsTab {
             scale_apply = scale_apply.on_press(SettingsMsg::ScaleApply.into());
         }
 
+        let site = self
+            .site
+            .get_or_insert_with(|| settings.site.clone())
+            .as_str();
+        let mut site_apply = Button::new(&mut self.site_apply, Text::new("Apply"));
+
+        if site != settings.site && Site::new(site).is_some() {
+            site_apply = site_apply.on_press(SettingsMsg::SiteApply.into());
+        }
+
+        let mut test_btn = Button::new(&mut self.test_btn, Text::new("Test webhook"))
+            .padding(8)
+            .style(settings.theme.primary_btn());
+
+        if !self.testing && settings.webhook.id != 0 && !settings.webhook.token.is_empty() {
+            test_btn = test_btn.on_press(SettingsMsg::TestWebhook.into());
+        }
+
+        let test_result = match self.test_result {
+            _ if self.testing => Text::new("Sending a sample embed..."),
+            Some(Ok(ref status)) => Text::new(format!("Delivered ({})", status))
+                .color(Color::from_rgb(0.0, 0.784, 0.325)),
+            Some(Err(ref err)) => {
+                Text::new(format!("Failed ({})", err)).color(Color::from_rgb(0.835, 0.0, 0.0))
+            }
+            None => Text::new("Paste a webhook URL into ID or Token to fill both")
+                .color(settings.theme.color_text_muted()),
+        };
+
+        let notifications = &settings.notifications;
+        let routing = Sink::ALL.iter().fold(
+            Column::new().spacing(8).push(
+                Event::ALL
+                    .iter()
+                    .fold(
+                        Row::new().push(Text::new("Routing").width(Length::FillPortion(2))),
+                        |row, event| {
+                            row.push(
+                                Text::new(event.to_string())
+                                    .width(Length::FillPortion(1))
+                                    .horizontal_alignment(HorizontalAlignment::Center),
+                            )
+                        },
+                    )
+                    .align_items(Align::Center),
+            ),
+            |column, &sink| {
+                let route = notifications.route(sink);
+
+                column.push(
+                    Event::ALL
+                        .iter()
+                        .fold(
+                            Row::new()
+                                .push(Text::new(sink.to_string()).width(Length::FillPortion(2))),
+                            |row, &event| {
+                                row.push(
+                                    Container::new(Checkbox::new(
+                                        route.allows(event),
+                                        "",
+                                        move |is| SettingsMsg::Route(sink, event, is).into(),
+                                    ))
+                                    .width(Length::FillPortion(1))
+                                    .center_x(),
+                                )
+                            },
+                        )
+                        .align_items(Align::Center),
+                )
+            },
+        );
+
         tab(&String::from("Settings"))
             .push(
                 section("Discord webhook", &settings.theme)
@@ -82,7 +247,12 @@ impl SettingsTab {
                                     |id| {
                                         match id.parse::<u64>() {
                                             Ok(id) => SettingsMsg::IdChanged(id),
-                                            Err(_) => SettingsMsg::None,
+                                            Err(_) => match Webhook::from_url(&id) {
+                                                Some(webhook) => {
+                                                    SettingsMsg::WebhookPasted(webhook)
+                                                }
+                                                None => SettingsMsg::None,
+                                            },
                                         }
                                         .into()
                                     },
@@ -101,30 +271,216 @@ impl SettingsTab {
                                     &mut self.token_input,
                                     "",
                                     &settings.webhook.token,
-                                    |token| SettingsMsg::TokenChanged(token).into(),
+                                    |token| {
+                                        match Webhook::from_url(&token) {
+                                            Some(webhook) => SettingsMsg::WebhookPasted(webhook),
+                                            None => SettingsMsg::TokenChanged(token),
+                                        }
+                                        .into()
+                                    },
                                 )
                                 .width(Length::FillPortion(2))
                                 .padding(8)
                                 .style(settings.theme.text_input()),
                             )
                             .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(test_btn)
+                            .push(test_result)
+                            .align_items(Align::Center)
+                            .spacing(16),
                     ),
             )
             .push(
-                section("Connectivity", &settings.theme).push(
-                    Row::new()
-                        .push(Text::new("Proxy mode: ").width(Length::FillPortion(1)))
-                        .push(
-                            PickList::new(
-                                &mut self.proxy_mode,
-                                &ProxyMode::ALL[..],
-                                Some(settings.proxy_mode.clone()),
-                                Message::ProxyMode,
+                section("Notifications", &settings.theme)
+                    .push(
+                        Row::new()
+                            .push(Text::new("Telegram bot token").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.telegram_token_input,
+                                    "",
+                                    &notifications.telegram.token,
+                                    |token| SettingsMsg::TelegramToken(token).into(),
+                                )
+                                .width(Length::FillPortion(2))
+                                .padding(8)
+                                .style(settings.theme.text_input()),
                             )
-                            .width(Length::FillPortion(2)),
-                        )
-                        .align_items(Align::Center),
-                ),
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Telegram chat ID").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.telegram_chat_input,
+                                    "",
+                                    &notifications.telegram.chat,
+                                    |chat| SettingsMsg::TelegramChat(chat).into(),
+                                )
+                                .width(Length::FillPortion(2))
+                                .padding(8)
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("JSON webhook URL").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.json_url_input,
+                                    "The report is posted as JSON",
+                                    &notifications.json.url,
+                                    |url| SettingsMsg::JsonUrl(url).into(),
+                                )
+                                .width(Length::FillPortion(2))
+                                .padding(8)
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Slack webhook URL").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.slack_url_input,
+                                    "",
+                                    &notifications.slack.url,
+                                    |url| SettingsMsg::SlackUrl(url).into(),
+                                )
+                                .width(Length::FillPortion(2))
+                                .padding(8)
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Desktop notifications").width(Length::FillPortion(1)))
+                            .push(
+                                Container::new(Checkbox::new(
+                                    notifications.desktop.enabled,
+                                    "",
+                                    |is| SettingsMsg::Desktop(is).into(),
+                                ))
+                                .width(Length::FillPortion(2))
+                                .center_x(),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(routing),
+            )
+            .push(
+                section("Connectivity", &settings.theme)
+                    .push(
+                        Row::new()
+                            .push(Text::new("Proxy mode: ").width(Length::FillPortion(1)))
+                            .push(
+                                PickList::new(
+                                    &mut self.proxy_mode,
+                                    &ProxyMode::ALL[..],
+                                    Some(settings.proxy_mode.clone()),
+                                    Message::ProxyMode,
+                                )
+                                .width(Length::FillPortion(2)),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Site URL").width(Length::FillPortion(1)))
+                            .push(
+                                Row::new()
+                                    .push(
+                                        TextInput::new(
+                                            &mut self.site_input,
+                                            DEFAULT_SITE_URL,
+                                            site,
+                                            |url| SettingsMsg::SiteChange(url).into(),
+                                        )
+                                        .width(Length::Fill)
+                                        .padding(8)
+                                        .style(settings.theme.text_input()),
+                                    )
+                                    .push(site_apply.style(settings.theme.primary_btn()))
+                                    .width(Length::FillPortion(2))
+                                    .spacing(8)
+                                    .align_items(Align::Center),
+                            )
+                            .align_items(Align::Center),
+                    ),
+            )
+            .push(
+                section("Control API", &settings.theme)
+                    .push(
+                        Row::new()
+                            .push(Text::new("Enabled").width(Length::FillPortion(1)))
+                            .push(
+                                Container::new(Checkbox::new(settings.control.enabled, "", |is| {
+                                    SettingsMsg::ControlEnabled(is).into()
+                                }))
+                                .width(Length::FillPortion(2))
+                                .center_x(),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Port").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.control_port_input,
+                                    "Listens on 127.0.0.1 only",
+                                    &settings.control.port.to_string(),
+                                    |port| {
+                                        match port.parse::<u16>() {
+                                            Ok(port) => SettingsMsg::ControlPort(port),
+                                            Err(_) => SettingsMsg::None,
+                                        }
+                                        .into()
+                                    },
+                                )
+                                .width(Length::FillPortion(2))
+                                .padding(8)
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Token").width(Length::FillPortion(1)))
+                            .push(
+                                Row::new()
+                                    .push(
+                                        TextInput::new(
+                                            &mut self.control_token_input,
+                                            "Bearer token of the API clients",
+                                            &settings.control.token,
+                                            |token| SettingsMsg::ControlToken(token).into(),
+                                        )
+                                        .width(Length::Fill)
+                                        .padding(8)
+                                        .style(settings.theme.text_input()),
+                                    )
+                                    .push(
+                                        Button::new(
+                                            &mut self.control_generate_btn,
+                                            Text::new("Generate"),
+                                        )
+                                        .on_press(SettingsMsg::ControlGenerate.into())
+                                        .style(settings.theme.primary_btn()),
+                                    )
+                                    .width(Length::FillPortion(2))
+                                    .spacing(8)
+                                    .align_items(Align::Center),
+                            )
+                            .align_items(Align::Center),
+                    ),
             )
             .push(
                 section("Appearance", &settings.theme)
@@ -205,6 +561,32 @@ impl SettingsTab {
                             )
                             .align_items(Align::Center),
                     )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Start restored tasks").width(Length::FillPortion(1)))
+                            .push(
+                                Container::new(Checkbox::new(settings.autostart, "", |is| {
+                                    Message::ExperimentalBool(8, is)
+                                }))
+                                .width(Length::FillPortion(2))
+                                .center_x(),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(
+                                Text::new("Capture failed requests").width(Length::FillPortion(1)),
+                            )
+                            .push(
+                                Container::new(Checkbox::new(settings.capture, "", |is| {
+                                    Message::ExperimentalBool(9, is)
+                                }))
+                                .width(Length::FillPortion(2))
+                                .center_x(),
+                            )
+                            .align_items(Align::Center),
+                    )
                     .push(
                         Row::new()
                             .push(Text::new("Monitor").width(Length::FillPortion(1)))
@@ -238,6 +620,82 @@ impl SettingsTab {
                                 .style(settings.theme.text_input()),
                             )
                             .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Retry attempts").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.retry_attempts_input,
+                                    "Retries after a timeout or connection error",
+                                    &settings.retry.attempts.to_string(),
+                                    |num| match num.parse::<u32>() {
+                                        Ok(num) => Message::ExperimentalNumber(4, num as u64),
+                                        Err(_) => Message::None,
+                                    },
+                                )
+                                .padding(8)
+                                .width(Length::FillPortion(2))
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Retry backoff (ms)").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.retry_backoff_input,
+                                    "First delay, doubled on every retry",
+                                    &settings.retry.backoff.to_string(),
+                                    |ms| match ms.parse::<u64>() {
+                                        Ok(ms) => Message::ExperimentalNumber(5, ms),
+                                        Err(_) => Message::None,
+                                    },
+                                )
+                                .padding(8)
+                                .width(Length::FillPortion(2))
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Retry jitter (ms)").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.retry_jitter_input,
+                                    "Max random delay added to the backoff",
+                                    &settings.retry.jitter.to_string(),
+                                    |ms| match ms.parse::<u64>() {
+                                        Ok(ms) => Message::ExperimentalNumber(6, ms),
+                                        Err(_) => Message::None,
+                                    },
+                                )
+                                .padding(8)
+                                .width(Length::FillPortion(2))
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
+                    )
+                    .push(
+                        Row::new()
+                            .push(Text::new("Warmup lead (s)").width(Length::FillPortion(1)))
+                            .push(
+                                TextInput::new(
+                                    &mut self.warmup_lead_input,
+                                    "Warmup start before a scheduled drop",
+                                    &settings.warmup_lead.to_string(),
+                                    |secs| match secs.parse::<u64>() {
+                                        Ok(secs) => Message::ExperimentalNumber(7, secs),
+                                        Err(_) => Message::None,
+                                    },
+                                )
+                                .padding(8)
+                                .width(Length::FillPortion(2))
+                                .style(settings.theme.text_input()),
+                            )
+                            .align_items(Align::Center),
                     ),
             )
             .push(
@@ -0,0 +1,128 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hyper::{
+    body::to_bytes,
+    header::{CONTENT_TYPE, COOKIE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server,
+};
+use tokio::time::sleep;
+use url::form_urlencoded;
+
+use scenario::Scenario;
+
+mod routes;
+pub mod scenario;
+
+static AUTH_COOKIE: &str = "WILDAUTHNEW_V3=";
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Mock
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+pub struct Mock {
+    scenario: Scenario,
+    /// Prints a line per request
+    verbose: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Number of product availability checks
+    checks: u32,
+    /// (characteristic id, quantity)
+    basket: Vec<(u64, u64)>,
+    orders: u64,
+}
+
+impl Mock {
+    pub fn new(scenario: Scenario) -> Mock {
+        Mock {
+            scenario,
+            verbose: false,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Mock {
+        self.verbose = verbose;
+        self
+    }
+}
+
+/// Binds the stand-in to `addr` (port 0 picks a free one) and returns the bound address with the
+/// server future
+pub fn serve(
+    addr: &SocketAddr,
+    scenario: Scenario,
+) -> Result<
+    (
+        SocketAddr,
+        impl Future<Output = Result<(), hyper::Error>> + Send,
+    ),
+    hyper::Error,
+> {
+    serve_mock(addr, Mock::new(scenario))
+}
+
+/// Same as [`serve`] for a configured stand-in
+pub fn serve_mock(
+    addr: &SocketAddr,
+    mock: Mock,
+) -> Result<
+    (
+        SocketAddr,
+        impl Future<Output = Result<(), hyper::Error>> + Send,
+    ),
+    hyper::Error,
+> {
+    let mock = Arc::new(mock);
+    let server = Server::try_bind(addr)?.serve(make_service_fn(move |_| {
+        let mock = mock.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(mock.clone(), req))) }
+    }));
+
+    Ok((server.local_addr(), server))
+}
+
+async fn handle(mock: Arc<Mock>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if mock.scenario.latency != 0 {
+        sleep(Duration::from_millis(mock.scenario.latency)).await;
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+    let authorized = req
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .any(|c| c.to_str().unwrap_or_default().contains(AUTH_COOKIE));
+
+    let form: Vec<(String, String)> = if method == Method::POST {
+        match to_bytes(req.into_body()).await {
+            Ok(body) => form_urlencoded::parse(&body).into_owned().collect(),
+            Err(_) => Vec::new(),
+        }
+    } else {
+        Vec::new()
+    };
+
+    let (status, content_type, body) = mock.route(&method, &path, &query, &form, authorized);
+    if mock.verbose {
+        println!("{} {} -> {}", method, path, status.as_u16());
+    }
+
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap())
+}
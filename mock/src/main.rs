@@ -0,0 +1,54 @@
+use std::{env, net::SocketAddr, process::exit};
+
+use mock::{scenario::Scenario, serve_mock, Mock};
+
+static USAGE: &str = "Usage: mock [--addr <ip:port>] [--scenario <name|path.toml>]";
+
+#[tokio::main]
+async fn main() {
+    let mut addr: SocketAddr = ([127, 0, 0, 1], 8080).into();
+    let mut scenario = (String::from("success"), Scenario::default());
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => match args.next().map(|a| a.parse::<SocketAddr>()) {
+                Some(Ok(value)) => addr = value,
+                _ => fail("--addr expects <ip:port>"),
+            },
+            "--scenario" => match args.next() {
+                Some(value) => match Scenario::from_arg(&value) {
+                    Ok(loaded) => scenario = (value, loaded),
+                    Err(err) => fail(&format!("Can't load scenario \"{}\": {}", value, err)),
+                },
+                None => fail("--scenario expects a name or a path"),
+            },
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                println!("Built-in scenarios: {}", Scenario::BUILTIN.join(", "));
+                return;
+            }
+            _ => fail(&format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+
+    let (addr, server) = match serve_mock(&addr, Mock::new(scenario.1).verbose(true)) {
+        Ok(bound) => bound,
+        Err(err) => fail(&format!("Can't bind {}: {}", addr, err)),
+    };
+
+    println!(
+        "Mock Wildberries is listening on http://{} (scenario: {})",
+        addr, scenario.0
+    );
+    println!("Run SDP with SDP_SITE_URL=http://{} to use it", addr);
+
+    if let Err(err) = server.await {
+        fail(&err.to_string());
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    exit(1)
+}
@@ -0,0 +1,318 @@
+use chrono::{Duration, Local};
+use hyper::{Method, StatusCode};
+use serde_json::{json, Value};
+
+use crate::{
+    scenario::{OrderOutcome, TokenState},
+    Mock,
+};
+
+static JSON: &str = "application/json; charset=utf-8";
+static HTML: &str = "text/html; charset=utf-8";
+
+type Reply = (StatusCode, &'static str, String);
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Routes
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+impl Mock {
+    pub(crate) fn route(
+        &self,
+        method: &Method,
+        path: &str,
+        query: &str,
+        form: &[(String, String)],
+        authorized: bool,
+    ) -> Reply {
+        let authorized = authorized && self.scenario.token == TokenState::Valid;
+
+        match (method, path) {
+            (&Method::GET, "/lk/personalcabinet/data") => self.personal_cabinet(authorized),
+            (&Method::POST, "/geo/getuserlocationinfo") => ok(json!({
+                "resultState": 0,
+                "value": {
+                    "address": "Москва",
+                    "xinfo": "appType=1&curr=rub&dest=-1257786",
+                },
+            })),
+            (_, "/lk/basket/data") if !authorized => unauthorized(),
+            (&Method::GET, "/lk/basket/data") => self.basket(),
+            (_, "/lk/basket/spa/delete") if !authorized => unauthorized(),
+            (&Method::POST, "/lk/basket/spa/delete") => self.delete(form),
+            (_, "/product/addtobasket") if !authorized => unauthorized(),
+            (&Method::POST, "/product/addtobasket") => self.add(form),
+            (_, "/lk/basket/spa/submitorder") if !authorized => unauthorized(),
+            (&Method::POST, "/lk/basket/spa/submitorder") => self.submit(form),
+            (&Method::GET, "/lk/order/confirmed/data") => self.confirmed(query),
+            (&Method::GET, "/lk/payment/fail") => (
+                StatusCode::OK,
+                HTML,
+                format!(
+                    r#"<html><body><div class="payment-fail"><p class="field-validation-error">{}</p></div></body></html>"#,
+                    self.scenario.bank_error
+                ),
+            ),
+            (&Method::GET, _) if path.ends_with("/product/data") => self.product(path),
+            _ => (StatusCode::NOT_FOUND, HTML, String::from("Not Found")),
+        }
+    }
+
+    fn personal_cabinet(&self, authorized: bool) -> Reply {
+        if !authorized {
+            return unauthorized();
+        }
+
+        let account = &self.scenario.account;
+
+        ok(json!({
+            "resultState": 0,
+            "value": {
+                "data": { "basketInfo": self.basket_info() },
+                "userInfo": {
+                    "country": "ru",
+                    "lastName": account.last_name,
+                    "firstName": account.first_name,
+                    "middleName": "",
+                    "phone": account.phone,
+                    "formattedPhoneMobile": format!("+{}", account.phone),
+                    "someId": format!("mock-{}", account.phone),
+                },
+            },
+        }))
+    }
+
+    fn product(&self, path: &str) -> Reply {
+        let product = match path
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .and_then(|cod| cod.parse::<u64>().ok())
+            .and_then(|cod| self.scenario.product(cod))
+        {
+            Some(product) => product,
+            None => return (StatusCode::NOT_FOUND, HTML, String::from("Not Found")),
+        };
+
+        let checks = {
+            let mut state = self.state.lock().unwrap();
+            state.checks += 1;
+            state.checks
+        };
+        let sizes = product
+            .sizes
+            .iter()
+            .map(|s| {
+                let in_stock = s.in_stock(checks);
+
+                (
+                    s.id.to_string(),
+                    json!({
+                        "characteristicId": s.id,
+                        "sizeName": s.name,
+                        "price": s.price,
+                        "priceWithSale": s.sale_price,
+                        "quantity": if in_stock { s.quantity } else { 0 },
+                        "isSoldOut": !in_stock,
+                    }),
+                )
+            })
+            .collect::<serde_json::Map<String, Value>>();
+
+        ok(json!({
+            "resultState": 0,
+            "value": {
+                "data": {
+                    "productCard": { "goodsName": product.name },
+                    "selectedNomenclature": {
+                        "isSoldOut": product.sizes.iter().all(|s| !s.in_stock(checks)),
+                        "cod1S": product.cod,
+                        "rusName": product.variant,
+                        "sizes": sizes,
+                    },
+                },
+            },
+        }))
+    }
+
+    fn basket_info(&self) -> Value {
+        let quantity = self
+            .state
+            .lock()
+            .unwrap()
+            .basket
+            .iter()
+            .map(|(_, q)| q)
+            .sum::<u64>();
+
+        json!({ "isAuthenticated": true, "basketQuantity": quantity, "eventsCount": 0 })
+    }
+
+    fn total(&self) -> u64 {
+        self.state
+            .lock()
+            .unwrap()
+            .basket
+            .iter()
+            .map(|(id, quantity)| match self.scenario.size(*id) {
+                Some(size) => size.sale_price * quantity,
+                None => 0,
+            })
+            .sum()
+    }
+
+    fn basket(&self) -> Reply {
+        let delivery = Local::now().naive_local().date() + Duration::days(3);
+        let items = self
+            .state
+            .lock()
+            .unwrap()
+            .basket
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<u64>>();
+
+        ok(json!({
+            "resultState": 0,
+            "value": {
+                "data": {
+                    "basket": {
+                        "paymentType": { "id": "bankCard", "bankCardId": "mock-card-0001" },
+                        "deliveryWays": [{
+                            "code": "self",
+                            "calendars": [{
+                                "storeIds": [117986, 507],
+                                "shippingInterval": {
+                                    "intervalId": 1,
+                                    "deliveryDateShort": delivery.format("%-m/%-d/%Y").to_string(),
+                                },
+                            }],
+                        }],
+                        "deliveryWay": "self",
+                        "deliveryIntervalTxt": delivery.format("%d.%m").to_string(),
+                        "deliveryPoint": {
+                            "kladrId": 7700000000000u64,
+                            "address": "Москва, ул. Тестовая, д. 1",
+                        },
+                        "includeInOrder": items,
+                        "totalPriceToPay": self.total(),
+                    },
+                    "basketInfo": self.basket_info(),
+                },
+            },
+        }))
+    }
+
+    fn delete(&self, form: &[(String, String)]) -> Reply {
+        let ids = form
+            .iter()
+            .filter(|(k, _)| k.starts_with("chrtIds"))
+            .filter_map(|(_, v)| v.parse::<u64>().ok())
+            .collect::<Vec<u64>>();
+
+        self.state
+            .lock()
+            .unwrap()
+            .basket
+            .retain(|(id, _)| !ids.contains(id));
+
+        ok(json!({ "resultState": 0 }))
+    }
+
+    fn add(&self, form: &[(String, String)]) -> Reply {
+        let field = |name: &str| {
+            form.iter()
+                .find(|(k, _)| k == name)
+                .and_then(|(_, v)| v.parse::<u64>().ok())
+        };
+        let (product, id, quantity) = match (field("cod1S"), field("characteristicId")) {
+            (Some(cod), Some(id)) => match self.scenario.product(cod) {
+                Some(product) => (product, id, field("quantity").unwrap_or(1)),
+                None => return message("Товар не найден"),
+            },
+            _ => return message("Товар не найден"),
+        };
+
+        {
+            let mut state = self.state.lock().unwrap();
+
+            match product.size(id) {
+                Some(size) if size.in_stock(state.checks) => (),
+                _ => return message("Товар закончился"),
+            }
+
+            match state.basket.iter_mut().find(|(i, _)| *i == id) {
+                Some(item) => item.1 += quantity,
+                None => state.basket.push((id, quantity)),
+            }
+        }
+
+        ok(json!({
+            "resultState": 0,
+            "value": { "basketInfo": self.basket_info() },
+        }))
+    }
+
+    fn submit(&self, form: &[(String, String)]) -> Reply {
+        if self.state.lock().unwrap().basket.is_empty() {
+            return message("Корзина пуста");
+        }
+
+        let total = self.total().to_string();
+        match form.iter().find(|(k, _)| k == "orderDetails.TotalPrice") {
+            Some((_, price)) if price == &total => (),
+            _ => return message("Стоимость заказа изменилась"),
+        }
+
+        let order = {
+            let mut state = self.state.lock().unwrap();
+            state.orders += 1;
+            if self.scenario.order != OrderOutcome::Fail {
+                state.basket.clear();
+            }
+            state.orders
+        };
+
+        ok(json!({
+            "resultState": 0,
+            "value": {
+                "url": match self.scenario.order {
+                    OrderOutcome::Success | OrderOutcome::Unconfirmed => {
+                        format!("/lk/order/confirmed?orderId={}&paid=True", order)
+                    }
+                    OrderOutcome::Secure => {
+                        format!("https://beta.paywb.com/checkout/3ds?orderId={}", order)
+                    }
+                    OrderOutcome::Fail => String::from("/lk/payment/fail"),
+                },
+            },
+        }))
+    }
+
+    fn confirmed(&self, query: &str) -> Reply {
+        let orders = self.state.lock().unwrap().orders;
+        let known = query
+            .split('&')
+            .find_map(|p| p.strip_prefix("orderId="))
+            .and_then(|id| id.parse::<u64>().ok())
+            .is_some_and(|id| id != 0 && id <= orders);
+
+        if known && self.scenario.order == OrderOutcome::Success {
+            ok(json!({ "resultState": 0, "value": { "data": {} } }))
+        } else {
+            message("Заказ не найден")
+        }
+    }
+}
+
+fn ok(value: Value) -> Reply {
+    (StatusCode::OK, JSON, value.to_string())
+}
+
+fn message(text: &str) -> Reply {
+    ok(json!({ "resultState": -1, "value": text }))
+}
+
+fn unauthorized() -> Reply {
+    message("Требуется авторизация")
+}
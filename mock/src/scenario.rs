@@ -0,0 +1,220 @@
+use std::{fs::read_to_string, path::Path};
+
+use serde::Deserialize;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Scenario
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Describes how the stand-in behaves during a run. Loaded from TOML or picked from built-ins
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Scenario {
+    pub token: TokenState,
+    pub order: OrderOutcome,
+    pub bank_error: String,
+    /// Delay (ms) before every response
+    pub latency: u64,
+
+    pub account: Account,
+    pub product: Product,
+    /// Served alongside `product`, e.g. for multi-item orders
+    pub products: Vec<Product>,
+}
+
+impl Scenario {
+    pub const BUILTIN: [&'static str; 6] = [
+        "success",
+        "unconfirmed",
+        "sold-out",
+        "expired-token",
+        "secure",
+        "bank-failure",
+    ];
+
+    pub fn builtin(name: &str) -> Option<Scenario> {
+        let mut scenario = Scenario::default();
+
+        match name {
+            "success" => (),
+            "unconfirmed" => scenario.order = OrderOutcome::Unconfirmed,
+            "sold-out" => {
+                // "M" sells out after the first availability check, "L" is never in stock
+                scenario.product.sizes[0].sold_out_after = Some(1);
+                scenario.product.sizes[1].quantity = 0;
+            }
+            "expired-token" => scenario.token = TokenState::Expired,
+            "secure" => scenario.order = OrderOutcome::Secure,
+            "bank-failure" => scenario.order = OrderOutcome::Fail,
+            _ => return None,
+        }
+
+        Some(scenario)
+    }
+
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        match read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// Product with `cod` among all served ones
+    pub fn product(&self, cod: u64) -> Option<&Product> {
+        std::iter::once(&self.product)
+            .chain(&self.products)
+            .find(|p| p.cod == cod)
+    }
+
+    /// Size with `id` among all served products
+    pub fn size(&self, id: u64) -> Option<&Size> {
+        std::iter::once(&self.product)
+            .chain(&self.products)
+            .find_map(|p| p.size(id))
+    }
+
+    /// Built-in scenario name or path to a TOML file
+    pub fn from_arg(arg: &str) -> Result<Scenario, String> {
+        match Scenario::builtin(arg) {
+            Some(scenario) => Ok(scenario),
+            None => Scenario::load(Path::new(arg)),
+        }
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            token: TokenState::Valid,
+            order: OrderOutcome::Success,
+            bank_error: String::from("Недостаточно средств на карте"),
+            latency: 0,
+            account: Account::default(),
+            product: Product::default(),
+            products: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenState {
+    Valid,
+    Expired,
+}
+
+/// What `submitorder` redirects to
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrderOutcome {
+    /// Order page, confirmed by `order/confirmed/data`
+    Success,
+    /// Order page, but `order/confirmed/data` fails
+    Unconfirmed,
+    /// 3-D Secure page on `beta.paywb.com`
+    Secure,
+    /// `payment/fail` page with `bank_error`
+    Fail,
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Account
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Account {
+    pub phone: u64,
+    pub first_name: String,
+    pub last_name: String,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            phone: 79990000000,
+            first_name: String::from("Test"),
+            last_name: String::from("Account"),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Product
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Product {
+    pub cod: u64,
+    pub name: String,
+    pub variant: Option<String>,
+    pub sizes: Vec<Size>,
+}
+
+impl Default for Product {
+    fn default() -> Self {
+        Product {
+            cod: 10000001,
+            name: String::from("Mock Sneakers"),
+            variant: Some(String::from("белый")),
+            sizes: vec![
+                Size {
+                    id: 20000001,
+                    name: String::from("M"),
+                    ..Default::default()
+                },
+                Size {
+                    id: 20000002,
+                    name: String::from("L"),
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+}
+
+impl Product {
+    pub fn size(&self, id: u64) -> Option<&Size> {
+        self.sizes.iter().find(|s| s.id == id)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Size {
+    pub id: u64,
+    pub name: String,
+    pub price: u64,
+    pub sale_price: u64,
+    pub quantity: u64,
+    /// Number of availability checks (product data requests) before the size comes in stock
+    pub available_after: u32,
+    /// Number of availability checks after which the size is sold out
+    pub sold_out_after: Option<u32>,
+}
+
+impl Size {
+    pub fn in_stock(&self, checks: u32) -> bool {
+        self.quantity != 0
+            && checks >= self.available_after
+            && match self.sold_out_after {
+                Some(after) => checks < after,
+                None => true,
+            }
+    }
+}
+
+impl Default for Size {
+    fn default() -> Self {
+        Size {
+            id: 0,
+            name: String::new(),
+            price: 4990,
+            sale_price: 3990,
+            quantity: 10,
+            available_after: 0,
+            sold_out_after: None,
+        }
+    }
+}
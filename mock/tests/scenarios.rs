@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use api::{client::WildberriesClient, error::ErrorKind, models::ResponseValue, site::Site};
+use mock::{scenario::Scenario, serve};
+
+static TOKEN: &str = "MOCKTOKEN";
+
+async fn client(scenario: Scenario) -> WildberriesClient {
+    let (addr, server) = serve(&([127, 0, 0, 1], 0).into(), scenario).unwrap();
+    tokio::spawn(server);
+
+    WildberriesClient::new(
+        Site::new(&format!("http://{}", addr)).unwrap(),
+        None,
+        Some(TOKEN),
+    )
+}
+
+/// Adds the first size to the basket and submits the order, returns the redirect url
+async fn checkout(client: &mut WildberriesClient, scenario: &Scenario) -> String {
+    let size = &scenario.product.sizes[0];
+
+    let result = client
+        .add_to_basket(scenario.product.cod, size.id, 1, 0)
+        .await
+        .unwrap();
+    match result.value {
+        ResponseValue::Basket(data) => assert_eq!(data.basket_info.unwrap().quantity, 1),
+        value => panic!("unexpected addtobasket value: {:?}", value),
+    }
+
+    let basket = match client.basket_data(0).await.unwrap().value {
+        ResponseValue::Value(value) => value.data.basket.unwrap(),
+        value => panic!("unexpected basket value: {:?}", value),
+    };
+    assert_eq!(basket.order_items, vec![size.id]);
+    assert_eq!(basket.total_price, size.sale_price);
+    assert_eq!(basket.delivery_ways[0].code, basket.delivery_way);
+
+    let result = client
+        .submit_order(
+            &vec![(
+                String::from("orderDetails.TotalPrice"),
+                basket.total_price.to_string(),
+            )],
+            0,
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.state, 0);
+
+    match result.value {
+        ResponseValue::Order { url } => url,
+        value => panic!("unexpected submitorder value: {:?}", value),
+    }
+}
+
+#[tokio::test]
+async fn success() {
+    let scenario = Scenario::builtin("success").unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    match client.personal_cabinet_data(0).await.unwrap().value {
+        ResponseValue::Value(value) => assert!(value.user.is_some()),
+        value => panic!("unexpected personalcabinet value: {:?}", value),
+    }
+    assert_eq!(client.user_location(0).await.unwrap().state, 0);
+
+    match client
+        .product_data(scenario.product.cod, 0)
+        .await
+        .unwrap()
+        .value
+    {
+        ResponseValue::Value(value) => {
+            assert_eq!(value.data.product_card.unwrap().name, scenario.product.name);
+            assert_eq!(value.data.variant.unwrap().sizes_tags().len(), 2);
+        }
+        value => panic!("unexpected product value: {:?}", value),
+    }
+
+    let url = checkout(&mut client, &scenario).await;
+    assert!(url.contains("orderId=1&paid"));
+    assert_eq!(client.order_confirmed("1", 0).await.unwrap().state, 0);
+}
+
+#[tokio::test]
+async fn unknown_product() {
+    let mut client = client(Scenario::default()).await;
+
+    assert_eq!(
+        client.product_data(1, 0).await.unwrap_err().kind,
+        ErrorKind::NotFound
+    );
+}
+
+#[tokio::test]
+async fn sold_out() {
+    let scenario = Scenario::builtin("sold-out").unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    let variant = match client
+        .product_data(scenario.product.cod, 0)
+        .await
+        .unwrap()
+        .value
+    {
+        ResponseValue::Value(value) => value.data.variant.unwrap(),
+        value => panic!("unexpected product value: {:?}", value),
+    };
+    assert!(variant.sold_out);
+    assert!(variant.sizes.values().all(|s| s.sold_out));
+
+    let result = client
+        .add_to_basket(scenario.product.cod, scenario.product.sizes[0].id, 1, 0)
+        .await
+        .unwrap();
+    assert_eq!(result.state, -1);
+    assert!(matches!(result.value, ResponseValue::Message(_)));
+}
+
+#[tokio::test]
+async fn expired_token() {
+    let mut client = client(Scenario::builtin("expired-token").unwrap()).await;
+
+    let result = client.personal_cabinet_data(0).await.unwrap();
+    assert_eq!(result.state, -1);
+    assert!(matches!(result.value, ResponseValue::Message(_)));
+    assert_eq!(client.basket_data(0).await.unwrap().state, -1);
+}
+
+#[tokio::test]
+async fn unconfirmed() {
+    let scenario = Scenario::builtin("unconfirmed").unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    assert!(checkout(&mut client, &scenario).await.contains("orderId="));
+    assert_eq!(client.order_confirmed("1", 0).await.unwrap().state, -1);
+}
+
+#[tokio::test]
+async fn secure() {
+    let scenario = Scenario::builtin("secure").unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    assert!(checkout(&mut client, &scenario)
+        .await
+        .starts_with("https://beta.paywb.com"));
+}
+
+#[tokio::test]
+async fn bank_failure() {
+    let scenario = Scenario::builtin("bank-failure").unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    assert!(checkout(&mut client, &scenario)
+        .await
+        .ends_with("payment/fail"));
+    assert_eq!(
+        client.payment_fail(0).await.unwrap(),
+        Some(scenario.bank_error)
+    );
+}
+
+#[tokio::test]
+async fn restock() {
+    let scenario = Scenario::load(Path::new("scenarios/restock.toml")).unwrap();
+    let mut client = client(scenario.clone()).await;
+
+    let mut sold_out = Vec::new();
+    for _ in 0..7 {
+        match client
+            .product_data(scenario.product.cod, 0)
+            .await
+            .unwrap()
+            .value
+        {
+            ResponseValue::Value(value) => sold_out.push(value.data.variant.unwrap().sold_out),
+            value => panic!("unexpected product value: {:?}", value),
+        }
+    }
+
+    assert_eq!(sold_out, [true, true, false, false, false, true, true]);
+}